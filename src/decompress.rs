@@ -0,0 +1,277 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// How input files are decompressed. Each format is only decoded with its cargo feature, `gzip`,
+/// `zstd` or `bzip2`, enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Picks the format from the file extension, or from the first bytes of files without a known
+    /// extension.
+    #[default]
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    fn detect(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("bz2") => Compression::Bzip2,
+            _ => Compression::None,
+        }
+    }
+
+    /// Recognizes the format by the magic bytes at the start of `peeked`.
+    fn sniff(peeked: &[u8]) -> Self {
+        match peeked {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+            [b'B', b'Z', b'h', b'1'..=b'9', ..] => Compression::Bzip2,
+            _ => Compression::None,
+        }
+    }
+
+    /// Whether `peeked`, the start of an input, decodes as this format. Running out of input is
+    /// fine, the rest of the stream has not been read yet.
+    fn decodes(&self, peeked: &[u8]) -> bool {
+        let Ok(mut decoder) = self.decoder(Box::new(io::Cursor::new(peeked.to_vec()))) else {
+            return false;
+        };
+        match decoder.fill_buf() {
+            Ok(_) => true,
+            Err(err) => err.kind() == io::ErrorKind::UnexpectedEof,
+        }
+    }
+
+    fn is_supported(&self) -> bool {
+        match self {
+            Compression::Auto | Compression::None => true,
+            Compression::Gzip => cfg!(feature = "gzip"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+            Compression::Bzip2 => cfg!(feature = "bzip2"),
+        }
+    }
+
+    /// Wraps `reader` in a decoder for this format.
+    fn decoder(&self, reader: Box<dyn BufRead + Send>) -> io::Result<Box<dyn BufRead + Send>> {
+        match self {
+            Compression::Auto | Compression::None => Ok(reader),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?))),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Ok(Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader)))),
+            #[allow(unreachable_patterns)]
+            compression => Err(io::Error::new(io::ErrorKind::Unsupported, format!("{compression} support is not compiled in"))),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Compression::Auto),
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "bz2" | "bzip2" => Ok(Compression::Bzip2),
+            _ => Err(format!("unsupported compression {s}, expected one of auto, none, gzip, zstd, bz2")),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Auto => write!(f, "auto"),
+            Compression::None => write!(f, "none"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Bzip2 => write!(f, "bz2"),
+        }
+    }
+}
+
+/// Opens `path` for reading, `-` being standard input, and decompresses it as `compression` says.
+/// Standard input is only decompressed with an explicit format. With `Auto`, files that are not
+/// compressed, or compressed in a format that is not compiled in, are read as they are, and so are
+/// files whose first bytes only look like a compressed format.
+pub fn open(path: &str, compression: Compression) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader: Box<dyn BufRead + Send> = match path {
+        "-" => Box::new(BufReader::new(io::stdin())),
+        _ => Box::new(BufReader::new(File::open(path)?)),
+    };
+
+    let compression = match (compression, path) {
+        (Compression::Auto, "-") => Compression::None,
+        (Compression::Auto, _) => match Compression::detect(path) {
+            Compression::None => {
+                let peeked = reader.fill_buf()?;
+                match Compression::sniff(peeked) {
+                    sniffed if sniffed.is_supported() && sniffed.decodes(peeked) => sniffed,
+                    _ => Compression::None,
+                }
+            }
+            detected if detected.is_supported() => detected,
+            _ => Compression::None,
+        },
+        (compression, _) => compression,
+    };
+    compression.decoder(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    #[cfg(any(feature = "gzip", feature = "bzip2"))]
+    use std::io::Write;
+
+    use super::*;
+
+    const DATA: &[u8] = b"a,b\nc,d\n";
+
+    fn read(name: &str, contents: &[u8], compression: Compression) -> io::Result<Vec<String>> {
+        let path = std::env::temp_dir().join(format!("cccut-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+
+        open(path.to_str().unwrap(), compression)?.lines().collect()
+    }
+
+    #[test]
+    fn test_open_plain() {
+        let tests: Vec<(&str, &[u8])> = vec![
+            ("plain.txt", DATA),
+            ("plain.log", DATA),
+            ("short.log", b"a\n"),
+        ];
+
+        for (name, contents) in tests {
+            let lines = read(name, contents, Compression::Auto).unwrap();
+
+            assert_eq!(contents.lines().collect::<Result<Vec<_>, _>>().unwrap(), lines);
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_open_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(DATA).unwrap();
+        let contents = encoder.finish().unwrap();
+        let tests = vec![
+            ("compressed.gz", Compression::Auto),
+            ("sniffed-gzip.log", Compression::Auto),
+            ("explicit-gzip.data", Compression::Gzip),
+        ];
+
+        for (name, compression) in tests {
+            let lines = read(name, &contents, compression).unwrap();
+
+            assert_eq!(vec!["a,b", "c,d"], lines);
+        }
+    }
+
+    #[cfg(all(unix, feature = "gzip"))]
+    #[test]
+    fn test_open_fifo() {
+        let path = std::env::temp_dir().join(format!("cccut-{}-fifo.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(std::process::Command::new("mkfifo").arg(&path).status().unwrap().success());
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(DATA).unwrap();
+        let contents = encoder.finish().unwrap();
+
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || File::options().write(true).open(path).unwrap().write_all(&contents).unwrap())
+        };
+        let lines = open(path.to_str().unwrap(), Compression::Auto).unwrap().lines().collect::<Result<Vec<_>, _>>().unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(vec!["a,b", "c,d"], lines);
+    }
+
+    #[test]
+    fn test_open_lookalike() {
+        let tests: Vec<(&str, &[u8])> = vec![
+            ("lookalike-gzip.log", b"\x1f\x8b is not gzip\n"),
+            ("lookalike-zstd.log", b"\x28\xb5\x2f\xfd is not zstd\n"),
+            ("lookalike-bzip2.log", b"BZh9 is not bzip2\n"),
+        ];
+
+        for (name, contents) in tests {
+            let path = std::env::temp_dir().join(format!("cccut-{}-{name}", std::process::id()));
+            std::fs::write(&path, contents).unwrap();
+            let mut read = Vec::new();
+
+            open(path.to_str().unwrap(), Compression::Auto).unwrap().read_to_end(&mut read).unwrap();
+
+            assert_eq!(contents, read);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_open_zstd() {
+        let contents = zstd::encode_all(DATA, 0).unwrap();
+        let tests = vec![
+            ("compressed.zst", Compression::Auto),
+            ("sniffed-zstd.log", Compression::Auto),
+            ("explicit-zstd.data", Compression::Zstd),
+        ];
+
+        for (name, compression) in tests {
+            let lines = read(name, &contents, compression).unwrap();
+
+            assert_eq!(vec!["a,b", "c,d"], lines);
+        }
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn test_open_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(DATA).unwrap();
+        let contents = encoder.finish().unwrap();
+        let tests = vec![
+            ("compressed.bz2", Compression::Auto),
+            ("sniffed-bzip2.log", Compression::Auto),
+            ("explicit-bzip2.data", Compression::Bzip2),
+        ];
+
+        for (name, compression) in tests {
+            let lines = read(name, &contents, compression).unwrap();
+
+            assert_eq!(vec!["a,b", "c,d"], lines);
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_open_corrupt() {
+        let result = read("corrupt.gz", b"not gzip", Compression::Auto);
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_open_without_gzip() {
+        let result = read("unsupported.data", DATA, Compression::Gzip);
+        assert_eq!(io::ErrorKind::Unsupported, result.unwrap_err().kind());
+
+        let lines = read("unsupported.gz", DATA, Compression::Auto).unwrap();
+        assert_eq!(vec!["a,b", "c,d"], lines);
+    }
+}
@@ -0,0 +1,148 @@
+//! Encodes the output of `--output-encoding`. The encoders are written by hand rather than taken
+//! from `encoding_rs`: it only encodes to UTF-16 by writing UTF-8, maps the latin1 label to
+//! windows-1252, and replaces unmappable characters with HTML character references, so none of
+//! the encodings or policies offered here would come out as asked.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// What to do with characters the target encoding can not represent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Unmappable {
+    #[default]
+    Replace,
+    Skip,
+    Error,
+}
+
+impl Encoding {
+    pub fn encode(&self, text: &str, unmappable: Unmappable) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Latin1 => {
+                let mut output = Vec::with_capacity(text.len());
+                for c in text.chars() {
+                    match u8::try_from(c) {
+                        Ok(byte) => output.push(byte),
+                        Err(_) => match unmappable {
+                            Unmappable::Replace => output.push(b'?'),
+                            Unmappable::Skip => {}
+                            Unmappable::Error => return Err(format!("character {c:?} can not be encoded as {self}")),
+                        },
+                    }
+                }
+                Ok(output)
+            }
+            Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(Encoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            "utf16le" | "utf-16le" => Ok(Encoding::Utf16Le),
+            "utf16be" | "utf-16be" => Ok(Encoding::Utf16Be),
+            _ => Err(format!("unsupported encoding {s}, expected one of utf8, latin1, utf16le, utf16be")),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "utf8"),
+            Encoding::Latin1 => write!(f, "latin1"),
+            Encoding::Utf16Le => write!(f, "utf16le"),
+            Encoding::Utf16Be => write!(f, "utf16be"),
+        }
+    }
+}
+
+impl FromStr for Unmappable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(Unmappable::Replace),
+            "skip" => Ok(Unmappable::Skip),
+            "error" => Ok(Unmappable::Error),
+            _ => Err(format!("unsupported policy {s}, expected one of replace, skip, error")),
+        }
+    }
+}
+
+impl Display for Unmappable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unmappable::Replace => write!(f, "replace"),
+            Unmappable::Skip => write!(f, "skip"),
+            Unmappable::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        struct TestCase {
+            encoding: Encoding,
+            unmappable: Unmappable,
+            text: &'static str,
+            expected: Result<Vec<u8>, String>,
+        }
+        let tests = vec![
+            TestCase {
+                encoding: Encoding::Latin1,
+                unmappable: Unmappable::Error,
+                text: "café",
+                expected: Ok(vec![b'c', b'a', b'f', 0xE9]),
+            },
+            TestCase {
+                encoding: Encoding::Latin1,
+                unmappable: Unmappable::Replace,
+                text: "5€",
+                expected: Ok(vec![b'5', b'?']),
+            },
+            TestCase {
+                encoding: Encoding::Latin1,
+                unmappable: Unmappable::Skip,
+                text: "5€",
+                expected: Ok(vec![b'5']),
+            },
+            TestCase {
+                encoding: Encoding::Latin1,
+                unmappable: Unmappable::Error,
+                text: "5€",
+                expected: Err(String::from("character '€' can not be encoded as latin1")),
+            },
+            TestCase {
+                encoding: Encoding::Utf16Le,
+                unmappable: Unmappable::Error,
+                text: "a€",
+                expected: Ok(vec![b'a', 0x00, 0xAC, 0x20]),
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(test.expected, test.encoding.encode(test.text, test.unmappable));
+        }
+    }
+}
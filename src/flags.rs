@@ -1,12 +1,26 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
 use std::fmt::{Debug, Display, Formatter};
+use std::io;
+use std::io::Write;
 use std::rc::Rc;
 use std::str::FromStr;
 
 pub trait Value {
     fn parse_from_string(&mut self, s: &str) -> Result<(), String>;
     fn try_activate(&mut self) -> Result<(), String>;
+
+    /// Resets a bool value to false, used by `+x` arguments.
+    fn try_deactivate(&mut self) -> Result<(), String> {
+        Err(String::from("bound value should be of type bool"))
+    }
+
+    /// The value shown as default in the usage, if any.
+    fn default_value(&self) -> Option<String> {
+        None
+    }
 }
 
 
@@ -31,12 +45,42 @@ impl<T> Value for T
             _ => Err(String::from("bound value should be of type bool"))
         }
     }
+
+    fn try_deactivate(&mut self) -> Result<(), String> {
+        let t = self.to_string();
+        match t.as_str() {
+            "true" | "false" => self.parse_from_string("false"),
+            _ => Err(String::from("bound value should be of type bool"))
+        }
+    }
+
+    fn default_value(&self) -> Option<String> {
+        // An unset bool flag is false anyway.
+        Some(self.to_string()).filter(|value| !value.is_empty() && value != "false")
+    }
 }
 
 
+/// Parses a value and appends it, used by the vectors bound with `FlagSet::bind_vec`.
+trait Push {
+    fn push_parsed(&mut self, arg: &str) -> Result<(), String>;
+}
+
+impl<T> Push for Vec<T>
+    where T: FromStr, <T as FromStr>::Err: Debug {
+    fn push_parsed(&mut self, arg: &str) -> Result<(), String> {
+        let value = T::from_str(arg).map_err(|err| format!("{:?}", err))?;
+        self.push(value);
+        Ok(())
+    }
+}
+
 enum ValueRef<'a> {
     MutRef(&'a mut dyn Value),
     RefCell(Rc<RefCell<dyn Value>>),
+    Vec(&'a mut dyn Push),
+    Choice(&'a mut String, &'a [&'a str]),
+    Count(&'a mut usize),
 }
 
 impl<'a> ValueRef<'a> {
@@ -44,6 +88,12 @@ impl<'a> ValueRef<'a> {
         match self {
             ValueRef::MutRef(inner) => inner.parse_from_string(s),
             ValueRef::RefCell(inner) => inner.borrow_mut().parse_from_string(s),
+            ValueRef::Vec(inner) => inner.push_parsed(s),
+            ValueRef::Choice(inner, allowed) => match allowed.contains(&s) {
+                true => inner.parse_from_string(s),
+                false => Err(format!("invalid value {s}, expected one of {}", allowed.join(", "))),
+            },
+            ValueRef::Count(inner) => inner.parse_from_string(s),
         }
     }
 
@@ -51,6 +101,33 @@ impl<'a> ValueRef<'a> {
         match self {
             ValueRef::MutRef(inner) => inner.try_activate(),
             ValueRef::RefCell(inner) => inner.borrow_mut().try_activate(),
+            ValueRef::Vec(_) | ValueRef::Choice(..) => Err(String::from("bound value should be of type bool")),
+            ValueRef::Count(inner) => {
+                **inner += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn try_deactivate(&mut self) -> Result<(), String> {
+        match self {
+            ValueRef::MutRef(inner) => inner.try_deactivate(),
+            ValueRef::RefCell(inner) => inner.borrow_mut().try_deactivate(),
+            ValueRef::Vec(_) | ValueRef::Choice(..) => Err(String::from("bound value should be of type bool")),
+            ValueRef::Count(inner) => {
+                **inner = 0;
+                Ok(())
+            }
+        }
+    }
+
+    fn default_value(&self) -> Option<String> {
+        match self {
+            ValueRef::MutRef(inner) => inner.default_value(),
+            ValueRef::RefCell(inner) => inner.borrow().default_value(),
+            ValueRef::Vec(_) => None,
+            ValueRef::Choice(inner, _) => inner.default_value(),
+            ValueRef::Count(inner) => Some(inner.to_string()).filter(|count| count != "0"),
         }
     }
 }
@@ -59,12 +136,15 @@ struct Flag<'a> {
     name: &'a str,
     inner: ValueRef<'a>,
     usage: &'a str,
+    /// The bound value at the time the flag was registered.
+    default: Option<String>,
 }
 
 impl<'a> Flag<'a> {
     fn new(name: &'a str, inner: ValueRef<'a>, usage: &'a str) -> Self {
         Self {
             name,
+            default: inner.default_value(),
             inner,
             usage,
         }
@@ -81,10 +161,52 @@ fn parse_name(value: &str) -> Option<&str> {
     }
 }
 
+/// The first character of `flag`, which may be longer than one byte.
+fn short_name(flag: &str) -> &str {
+    let len = flag.chars().next().map_or(0, char::len_utf8);
+    &flag[..len]
+}
+
+/// Levenshtein distance between `a` and `b`, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 #[derive(Debug)]
 pub enum FlagError {
     UnknownFlag(String),
+    /// An unknown name and the registered long name close to it.
+    UnknownFlagSuggest(String, String),
     ParseError((String, String)),
+    ExclusiveFlags(Vec<String>),
+    InvalidUnicode(String),
+    MissingFlag(String),
+    /// A prefix of several long names and the names it matches.
+    AmbiguousFlag(String, Vec<String>),
+    /// `--help` was given, the caller prints the usage.
+    HelpRequested,
+}
+
+impl Error for FlagError {}
+
+/// Everything `FlagSet::parse_os` found: the positional arguments and the flags that were set.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseReport {
+    pub remaining: Vec<OsString>,
+    /// Long names of the flags set on the command line, in the order they first appeared.
+    pub set_flags: Vec<String>,
 }
 
 impl Display for FlagError {
@@ -93,9 +215,27 @@ impl Display for FlagError {
             FlagError::UnknownFlag(name) => {
                 write!(f, "unknown flag: {name}")
             }
+            FlagError::UnknownFlagSuggest(name, suggestion) => {
+                write!(f, "unknown flag: {name} (did you mean: {suggestion}?)")
+            }
             FlagError::ParseError((name, err)) => {
                 write!(f, "could not parse flag {name} err: {err}")
             }
+            FlagError::ExclusiveFlags(names) => {
+                write!(f, "flags can not be used together: {}", names.join(", "))
+            }
+            FlagError::InvalidUnicode(arg) => {
+                write!(f, "flag argument is not valid unicode: {arg}")
+            }
+            FlagError::MissingFlag(name) => {
+                write!(f, "missing required flag: {name}")
+            }
+            FlagError::AmbiguousFlag(name, candidates) => {
+                write!(f, "ambiguous flag: {name} (could be: {})", candidates.join(", "))
+            }
+            FlagError::HelpRequested => {
+                write!(f, "help requested")
+            }
         }
     }
 }
@@ -103,13 +243,18 @@ impl Display for FlagError {
 #[derive(Default)]
 pub struct FlagSet<'a> {
     inner: HashMap<&'a str, Flag<'a>>,
+    exclusive_groups: Vec<Vec<&'a str>>,
+    required: Vec<&'a str>,
+    help: bool,
+    /// Extra names registered by `bind_aliases`, mapped to the key of their flag.
+    aliases: HashMap<&'a str, &'a str>,
 }
 
 impl<'a> FlagSet<'a>
 {
     pub fn bind_mut_ref(&mut self, flag: &'a str, allow_short: bool, value: &'a mut dyn Value, usage: &'a str) {
         let key = if allow_short {
-            &flag[..1]
+            short_name(flag)
         } else {
             flag
         };
@@ -120,9 +265,71 @@ impl<'a> FlagSet<'a>
         }
     }
 
+    /// Like `bind_mut_ref`, but registers `short` instead of the first letter of `flag` as the
+    /// short name.
+    pub fn bind_mut_ref_with_short(&mut self, flag: &'a str, short: &'a str, value: &'a mut dyn Value, usage: &'a str) {
+        let flag = Flag::new(flag, ValueRef::MutRef(value), usage);
+        if self.inner.insert(short, flag).is_some() {
+            panic!("should not register flag name {short} twice")
+        }
+    }
+
+    /// Binds one value to all of `names`, e.g. `["d", "delimiter"]`. The longest name is the one
+    /// reported in errors and `ParseReport::set_flags`.
+    pub fn bind_aliases(&mut self, names: &[&'a str], value: &'a mut dyn Value, usage: &'a str) {
+        let mut names = names.to_vec();
+        names.sort_by_key(|name| name.chars().count());
+        let (Some(key), Some(name)) = (names.first().copied(), names.last().copied()) else {
+            panic!("should bind at least one name")
+        };
+
+        for alias in &names {
+            if self.has_flag(alias) {
+                panic!("should not register flag name {alias} twice")
+            }
+        }
+
+        self.inner.insert(key, Flag::new(name, ValueRef::MutRef(value), usage));
+        for alias in &names[1..] {
+            self.aliases.insert(alias, key);
+        }
+    }
+
+    /// Binds a repeatable flag, every occurrence appends its value to `values`.
+    pub fn bind_vec<T>(&mut self, flag: &'a str, allow_short: bool, values: &'a mut Vec<T>, usage: &'a str)
+        where T: FromStr, <T as FromStr>::Err: Debug {
+        let key = if allow_short {
+            short_name(flag)
+        } else {
+            flag
+        };
+
+        let flag = Flag::new(flag, ValueRef::Vec(values), usage);
+        if self.inner.insert(key, flag).is_some() {
+            panic!("should not register flag name {key} twice")
+        }
+    }
+
+    /// Binds a flag that only accepts one of the `allowed` values.
+    pub fn bind_choice(&mut self, flag: &'a str, allowed: &'a [&'a str], value: &'a mut String, usage: &'a str) {
+        let choice = Flag::new(flag, ValueRef::Choice(value, allowed), usage);
+        if self.inner.insert(flag, choice).is_some() {
+            panic!("should not register flag name {flag} twice")
+        }
+    }
+
+    /// Binds a flag that counts its occurrences, so `-vvv` and `-v -v -v` both set `count` to 3.
+    pub fn bind_count(&mut self, flag: &'a str, count: &'a mut usize, usage: &'a str) {
+        let key = short_name(flag);
+        let flag = Flag::new(flag, ValueRef::Count(count), usage);
+        if self.inner.insert(key, flag).is_some() {
+            panic!("should not register flag name {key} twice")
+        }
+    }
+
     pub fn bind_ref_cell(&mut self, flag: &'a str, allow_short: bool, value: Rc<RefCell<dyn Value>>, usage: &'a str) {
         let key = if allow_short {
-            &flag[..1]
+            short_name(flag)
         } else {
             flag
         };
@@ -133,23 +340,148 @@ impl<'a> FlagSet<'a>
         }
     }
 
+    /// Makes `parse` fail when more than one of the named flags is set.
+    pub fn add_exclusive_group(&mut self, names: &[&'a str]) {
+        self.exclusive_groups.push(names.to_vec());
+    }
+
+    /// Makes `parse` return `FlagError::HelpRequested` on `--help`, or `-h` unless another flag
+    /// uses it.
+    pub fn add_help(&mut self) {
+        self.help = true;
+    }
+
+    fn is_help(&self, arg: &str) -> bool {
+        self.help && (arg == "--help" || arg == "-h" && !self.has_flag("h"))
+    }
+
+    /// Makes `parse` fail when the flag with this long name is not set.
+    pub fn add_required(&mut self, name: &'a str) {
+        self.required.push(name);
+    }
+
     fn has_flag(&self, name: &str) -> bool {
-        if self.inner.get(name).is_some() {
-            return true;
+        self.key(name).is_some()
+    }
+
+    fn key(&self, name: &str) -> Option<&'a str> {
+        if let Some((key, _)) = self.inner.get_key_value(name) {
+            return Some(*key);
+        }
+        if let Some(key) = self.aliases.get(name) {
+            return Some(*key);
+        }
+
+        self.inner.iter()
+            .find(|(_, flag)| flag.name == name)
+            .map(|(key, _)| *key)
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut Flag<'a>> {
+        let key = self.key(name)?;
+        self.inner.get_mut(key)
+    }
+
+    /// Splits `+abc` into the short names it resets, if all of them are registered. Anything else
+    /// starting with `+` is left to be a positional argument.
+    fn negated_short_names(&self, arg: &str) -> Option<Vec<String>> {
+        let names = arg.strip_prefix('+')?;
+        let names = names.chars().map(String::from).collect::<Vec<_>>();
+        match !names.is_empty() && names.iter().all(|name| self.has_flag(name)) {
+            true => Some(names),
+            false => None,
+        }
+    }
+
+    fn check_exclusive_groups(&self, seen: &[&str]) -> Result<(), FlagError> {
+        for group in &self.exclusive_groups {
+            let used = group.iter()
+                .filter(|name| seen.contains(name))
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+
+            if used.len() > 1 {
+                return Err(FlagError::ExclusiveFlags(used));
+            }
         }
 
-        if let Some(flag) = self.inner.get(&name[..1]) {
-            return flag.name == name;
+        Ok(())
+    }
+
+    /// Resolves the name of a `--` argument to the long name of its flag. Besides the names
+    /// themselves, any prefix matching the long names of only one flag is accepted.
+    fn complete(&self, name: &str) -> Result<&'a str, FlagError> {
+        if let Some(flag) = self.key(name).and_then(|key| self.inner.get(key)) {
+            return Ok(flag.name);
         }
 
-        false
+        let mut matches = self.inner.iter()
+            .map(|(key, flag)| (flag.name, *key))
+            .chain(self.aliases.iter().map(|(alias, key)| (*alias, *key)))
+            .filter(|(candidate, _)| !name.is_empty() && candidate.chars().count() > 1 && candidate.starts_with(name))
+            .map(|(_, key)| self.inner[key].name)
+            .collect::<Vec<_>>();
+        matches.sort_unstable();
+        matches.dedup();
+
+        match matches.as_slice() {
+            [] => Err(self.unknown_flag(name)),
+            [name] => Ok(name),
+            _ => Err(FlagError::AmbiguousFlag(name.to_string(), matches.iter().map(|name| name.to_string()).collect())),
+        }
+    }
+
+    /// Reports `name` as unknown, suggesting the registered long name closest to it.
+    fn unknown_flag(&self, name: &str) -> FlagError {
+        let suggestion = self.inner.values()
+            .map(|flag| flag.name)
+            .chain(self.help.then_some("help"))
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= 2)
+            .min();
+
+        match suggestion {
+            Some((_, suggestion)) => FlagError::UnknownFlagSuggest(name.to_string(), suggestion.to_string()),
+            None => FlagError::UnknownFlag(name.to_string()),
+        }
+    }
+
+    fn check_required(&self, seen: &[&str]) -> Result<(), FlagError> {
+        match self.required.iter().find(|name| !seen.contains(name)) {
+            Some(name) => Err(FlagError::MissingFlag(name.to_string())),
+            None => Ok(()),
+        }
     }
 
     pub fn parse(&mut self, args: impl IntoIterator<Item=String>) -> Result<Vec<String>, FlagError>
     {
+        let (remaining, _) = self.parse_with_seen(args.into_iter().map(OsString::from))?;
+        Ok(remaining.into_iter().map(|arg| arg.into_string().expect("arguments given as strings are valid unicode")).collect())
+    }
+
+    /// Like `parse`, but accepts arguments that are not valid unicode as long as they are
+    /// positional, and also reports which flags were set.
+    pub fn parse_os(&mut self, args: impl IntoIterator<Item=OsString>) -> Result<ParseReport, FlagError> {
+        let (remaining, seen) = self.parse_with_seen(args)?;
+
+        let mut set_flags = Vec::new();
+        for name in seen {
+            if !set_flags.contains(&name) {
+                set_flags.push(name);
+            }
+        }
+
+        Ok(ParseReport {
+            remaining,
+            set_flags: set_flags.into_iter().map(String::from).collect(),
+        })
+    }
+
+    fn parse_with_seen(&mut self, args: impl IntoIterator<Item=OsString>) -> Result<(Vec<OsString>, Vec<&'a str>), FlagError> {
         let mut remaining = Vec::new();
         let mut flag: Option<String> = None;
         let mut all_flags_parsed = false;
+        let mut seen = Vec::new();
 
         for arg in args {
             if arg == "--" {
@@ -161,59 +493,177 @@ impl<'a> FlagSet<'a>
                 continue;
             }
 
+            // Only positional arguments may be invalid unicode, once the first one is reached
+            // everything after it is positional too.
+            let arg = match arg.into_string() {
+                Ok(arg) => arg,
+                Err(arg) if flag.is_none() && !arg.as_encoded_bytes().starts_with(b"-") => {
+                    all_flags_parsed = true;
+                    remaining.push(arg);
+                    continue;
+                }
+                Err(arg) => return Err(FlagError::InvalidUnicode(arg.to_string_lossy().into_owned())),
+            };
+
             match flag {
                 Some(name) => {
-                    if let Some(value) = self.inner.get_mut(name.as_str()) {
+                    if let Some(value) = self.flag_mut(name.as_str()) {
                         value.inner
                             .parse_from_string(&arg)
                             .map_err(|err| FlagError::ParseError((name, err)))?;
+                        seen.push(value.name);
                     }
                     flag = None;
                 }
                 None => {
+                    if self.is_help(&arg) {
+                        return Err(FlagError::HelpRequested);
+                    }
+
+                    if let Some(names) = self.negated_short_names(&arg) {
+                        for short_name in names {
+                            if let Some(value) = self.flag_mut(short_name.as_str()) {
+                                value.inner
+                                    .try_deactivate()
+                                    .map_err(|err| FlagError::ParseError((short_name, err)))?;
+                                seen.retain(|name| *name != value.name);
+                            }
+                        }
+                        continue;
+                    }
+
+                    if let Some((name, arg)) = arg.strip_prefix("--").and_then(|arg| arg.split_once('=')) {
+                        let name = self.complete(name)?;
+                        if let Some(value) = self.flag_mut(name) {
+                            value.inner
+                                .parse_from_string(arg)
+                                .map_err(|err| FlagError::ParseError((name.to_string(), err)))?;
+                            seen.push(value.name);
+                        }
+                        continue;
+                    }
+
                     let name = parse_name(arg.as_str());
                     match name {
                         Some(name) => {
+                            // A long name that matches no flag is read as a cluster of short flags.
+                            let name = match arg.starts_with("--") {
+                                true => match self.complete(name) {
+                                    Err(FlagError::UnknownFlag(_) | FlagError::UnknownFlagSuggest(..)) => name,
+                                    completed => completed?,
+                                },
+                                false => name,
+                            };
+
                             if !self.has_flag(name) {
-                                for f in name.chars() {
+                                for (i, f) in name.char_indices() {
                                     let short_name = f.to_string();
 
                                     if !self.has_flag(short_name.as_str()) {
-                                        return Err(FlagError::UnknownFlag(name.to_string()));
+                                        return Err(self.unknown_flag(name));
                                     }
 
-                                    if let Some(value) = self.inner.get_mut(short_name.as_str()) {
+                                    if let Some(value) = self.flag_mut(short_name.as_str()) {
                                         if value.inner.try_activate().is_ok() {
+                                            seen.push(value.name);
                                             flag = None;
+                                            continue;
                                         }
+
+                                        // A flag that takes a value swallows the rest of the
+                                        // argument, as in `-d,`.
+                                        let attached = &name[i + f.len_utf8()..];
+                                        if attached.is_empty() {
+                                            flag = Some(short_name);
+                                            break;
+                                        }
+                                        value.inner
+                                            .parse_from_string(attached)
+                                            .map_err(|err| FlagError::ParseError((short_name, err)))?;
+                                        seen.push(value.name);
+                                        flag = None;
+                                        break;
                                     }
                                 }
+                                continue;
                             }
 
                             flag = Some(name.to_string());
 
-                            if let Some(value) = self.inner.get_mut(name) {
+                            if let Some(value) = self.flag_mut(name) {
                                 if value.inner.try_activate().is_ok() {
+                                    seen.push(value.name);
                                     flag = None;
                                 }
                             }
                         }
                         None => {
                             all_flags_parsed = true;
-                            remaining.push(arg);
+                            remaining.push(OsString::from(arg));
                         }
                     }
                 }
             }
         }
 
-        Ok(remaining)
+        self.check_exclusive_groups(&seen)?;
+        self.check_required(&seen)?;
+
+        Ok((remaining, seen))
     }
 
     pub fn print_usage(&self) {
-        for (name, flag) in &self.inner {
-            println!("{}\n\t{}", name, flag.usage)
+        self.print_usage_to(&mut io::stdout()).expect("should write usage to stdout")
+    }
+
+    /// Writes the usage to `w`, e.g. to standard error after a failed parse.
+    pub fn print_usage_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(self.usage().as_bytes())
+    }
+
+    /// One line per flag, sorted by name, with the descriptions aligned.
+    pub fn usage(&self) -> String {
+        let mut lines = self.inner.iter()
+            .map(|(key, flag)| {
+                let mut names = self.aliases.iter()
+                    .filter(|(_, alias_key)| *alias_key == key)
+                    .map(|(alias, _)| *alias)
+                    .chain([*key, flag.name])
+                    .collect::<Vec<_>>();
+                names.sort_by_key(|name| (name.chars().count(), *name));
+                names.dedup();
+                let names = names.iter()
+                    .map(|name| match name.chars().count() {
+                        1 => format!("-{name}"),
+                        _ => format!("--{name}"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let text = match &flag.default {
+                    Some(default) if flag.usage.is_empty() => format!("(default: {default})"),
+                    Some(default) => format!("{} (default: {default})", flag.usage),
+                    None => flag.usage.to_string(),
+                };
+                (flag.name, names, text)
+            })
+            .collect::<Vec<_>>();
+        if self.help {
+            let names = match self.has_flag("h") {
+                true => String::from("--help"),
+                false => String::from("-h, --help"),
+            };
+            lines.push(("help", names, String::from("print this help")));
+        }
+        lines.sort_by(|a, b| a.0.cmp(b.0));
+
+        let width = lines.iter().map(|(_, names, _)| names.chars().count()).max().unwrap_or(0);
+        let mut usage = String::new();
+        for (_, names, text) in lines {
+            let line = format!("  {names:<width$}  {text}");
+            usage.push_str(line.trim_end());
+            usage.push('\n');
         }
+        usage
     }
 }
 
@@ -384,23 +834,20 @@ mod tests {
                 let value = Rc::new(RefCell::new(String::new()));
                 actual.push(value.clone());
 
-                flag_set.bind_ref_cell(*name, false, value.clone(), "");
+                flag_set.bind_ref_cell(name, false, value.clone(), "");
             }
 
             let result = flag_set.parse(test.args.iter().map(|a| a.to_string()));
 
             assert_eq!(test.expected_flags.len(), actual.len());
-            for i in 0..actual.len() {
-                assert_eq!(test.expected_flags[i].1, *actual[i].borrow())
+            for (expected, actual) in test.expected_flags.iter().zip(&actual) {
+                assert_eq!(expected.1, *actual.borrow())
             }
 
             assert!(result.is_ok());
             let result = result.unwrap();
 
-            assert_eq!(test.remaining.len(), result.len());
-            for i in 0..result.len() {
-                assert_eq!(test.remaining[i], result[i]);
-            }
+            assert_eq!(test.remaining, result);
         }
     }
 
@@ -427,23 +874,647 @@ mod tests {
                 let value = Rc::new(RefCell::new(false));
                 actual.push(value.clone());
 
-                flag_set.bind_ref_cell(*name, false, value.clone(), "");
+                flag_set.bind_ref_cell(name, false, value.clone(), "");
             }
 
             let result = flag_set.parse(test.args.iter().map(|a| a.to_string()));
 
             assert_eq!(test.expected_flags.len(), actual.len());
-            for i in 0..actual.len() {
-                assert_eq!(test.expected_flags[i].1, *actual[i].borrow())
+            for (expected, actual) in test.expected_flags.iter().zip(&actual) {
+                assert_eq!(expected.1, *actual.borrow())
             }
 
             assert!(result.is_ok());
             let result = result.unwrap();
 
-            assert_eq!(test.remaining.len(), result.len());
-            for i in 0..result.len() {
-                assert_eq!(test.remaining[i], result[i]);
+            assert_eq!(test.remaining, result);
+        }
+    }
+
+    #[test]
+    fn test_exclusive_group() {
+        struct TestCase {
+            args: Vec<&'static str>,
+            expects_err: bool,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec!["-a", "-b"],
+                expects_err: true,
+            },
+            TestCase {
+                args: vec!["-a", "--count", "1"],
+                expects_err: true,
+            },
+            TestCase {
+                args: vec!["-b", "-c"],
+                expects_err: false,
+            },
+        ];
+
+        for test in tests {
+            let mut flag_set = FlagSet::default();
+
+            let mut a = false;
+            flag_set.bind_mut_ref("a", false, &mut a, "");
+            let mut b = false;
+            flag_set.bind_mut_ref("b", false, &mut b, "");
+            let mut c = false;
+            flag_set.bind_mut_ref("c", false, &mut c, "");
+            let mut count = 0;
+            flag_set.bind_mut_ref("count", false, &mut count, "");
+            flag_set.add_exclusive_group(&["a", "b", "count"]);
+
+            let result = flag_set.parse(test.args.iter().map(|a| a.to_string()));
+            assert_eq!(test.expects_err, result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_negated_short_flags() {
+        struct TestCase {
+            args: Vec<&'static str>,
+            expected: (bool, bool),
+            remaining: Vec<&'static str>,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec!["-s", "+s"],
+                expected: (false, false),
+                remaining: vec![],
+            },
+            TestCase {
+                args: vec!["-sv", "+s"],
+                expected: (false, true),
+                remaining: vec![],
+            },
+            TestCase {
+                args: vec!["+sv", "-v"],
+                expected: (false, true),
+                remaining: vec![],
+            },
+            TestCase {
+                args: vec!["-s", "+5"],
+                expected: (true, false),
+                remaining: vec!["+5"],
+            },
+        ];
+
+        for test in tests {
+            let mut flag_set = FlagSet::default();
+
+            let mut s = false;
+            flag_set.bind_mut_ref("s", false, &mut s, "");
+            let mut v = false;
+            flag_set.bind_mut_ref("v", false, &mut v, "");
+
+            let result = flag_set.parse(test.args.iter().map(|a| a.to_string())).unwrap();
+
+            assert_eq!(test.remaining, result);
+            assert_eq!(test.expected, (s, v));
+        }
+    }
+
+    #[test]
+    fn test_parse_os() {
+        let mut flag_set = FlagSet::default();
+
+        let mut verbose = false;
+        flag_set.bind_mut_ref("verbose", true, &mut verbose, "");
+        let mut quiet = false;
+        flag_set.bind_mut_ref("quiet", false, &mut quiet, "");
+        let mut count = 0;
+        flag_set.bind_mut_ref("count", false, &mut count, "");
+
+        let args = ["--count", "2", "-v", "--count", "3", "file", "--quiet"].map(OsString::from);
+        let report = flag_set.parse_os(args).unwrap();
+
+        let expected = ParseReport {
+            remaining: vec![OsString::from("file"), OsString::from("--quiet")],
+            set_flags: vec![String::from("count"), String::from("verbose")],
+        };
+        assert_eq!(expected, report);
+        assert_eq!(3, count);
+        assert!(!quiet);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_os_invalid_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut flag_set = FlagSet::default();
+        let mut name = String::new();
+        flag_set.bind_mut_ref("name", false, &mut name, "");
+
+        let invalid = OsString::from_vec(vec![b'f', 0xFF]);
+        let report = flag_set.parse_os([OsString::from("-name"), OsString::from("x"), invalid.clone()]).unwrap();
+        assert_eq!(vec![invalid.clone()], report.remaining);
+
+        let result = flag_set.parse_os([OsString::from("-name"), invalid]);
+        assert!(matches!(result, Err(FlagError::InvalidUnicode(_))));
+
+        drop(flag_set);
+        assert_eq!("x", name);
+    }
+
+    #[test]
+    fn test_bind_with_short() {
+        let tests = vec![
+            (vec!["-s"], true),
+            (vec!["--only-delimited"], true),
+            (vec!["-o"], false),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+
+            let mut value = false;
+            flag_set.bind_mut_ref_with_short("only-delimited", "s", &mut value, "");
+
+            let result = flag_set.parse(args.iter().map(|a| a.to_string()));
+            assert_eq!(expected, result.is_ok());
+
+            assert_eq!(expected, value);
+        }
+    }
+
+    #[test]
+    fn test_bind_vec() {
+        let tests = vec![
+            (vec!["-e", "a", "-e", "b"], vec!["a", "b"]),
+            (vec!["--expression", "a,b", "file"], vec!["a,b"]),
+            (vec!["file"], vec![]),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+
+            let mut values = Vec::<String>::new();
+            flag_set.bind_vec("expression", true, &mut values, "");
+
+            let result = flag_set.parse(args.iter().map(|a| a.to_string()));
+            assert!(result.is_ok());
+
+            assert_eq!(expected, values);
+        }
+
+        let mut flag_set = FlagSet::default();
+        let mut values = Vec::<i32>::new();
+        flag_set.bind_vec("n", false, &mut values, "");
+        assert!(flag_set.parse(["-n", "1", "-n", "x"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_parse_inline_value() {
+        struct TestCase {
+            args: Vec<&'static str>,
+            expected: Result<(&'static str, &'static str, bool), ()>,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec!["--delimiter=,"],
+                expected: Ok((",", "", false)),
+            },
+            TestCase {
+                args: vec!["--output-delimiter=a=b", "--csv=true"],
+                expected: Ok(("", "a=b", true)),
+            },
+            TestCase {
+                args: vec!["--delimiter=", "-o", "x"],
+                expected: Ok(("", "x", false)),
+            },
+            TestCase {
+                args: vec!["--unknown=1"],
+                expected: Err(()),
+            },
+        ];
+
+        for test in tests {
+            let mut flag_set = FlagSet::default();
+
+            let mut delimiter = String::new();
+            flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "");
+            let mut output_delimiter = String::new();
+            flag_set.bind_mut_ref("output-delimiter", true, &mut output_delimiter, "");
+            let mut csv = false;
+            flag_set.bind_mut_ref("csv", false, &mut csv, "");
+
+            let result = flag_set.parse(test.args.iter().map(|a| a.to_string()));
+
+            match test.expected {
+                Ok(expected) => {
+                    assert!(result.is_ok());
+                    assert_eq!(expected, (delimiter.as_str(), output_delimiter.as_str(), csv));
+                }
+                Err(_) => assert!(matches!(result, Err(FlagError::UnknownFlag(name)) if name == "unknown")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_attached_short_value() {
+        struct TestCase {
+            args: Vec<&'static str>,
+            expected: Option<(&'static str, &'static str, bool)>,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec!["-d,"],
+                expected: Some((",", "", false)),
+            },
+            TestCase {
+                args: vec!["-f1,2,3", "-d", ";"],
+                expected: Some((";", "1,2,3", false)),
+            },
+            TestCase {
+                args: vec!["-sd,", "-f1,2,3"],
+                expected: Some((",", "1,2,3", true)),
+            },
+            TestCase {
+                args: vec!["-d=x"],
+                expected: Some(("=x", "", false)),
+            },
+            TestCase {
+                args: vec!["-xd,"],
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            let mut flag_set = FlagSet::default();
+
+            let mut delimiter = String::new();
+            flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "");
+            let mut fields = String::new();
+            flag_set.bind_mut_ref("fields", true, &mut fields, "");
+            let mut separated = false;
+            flag_set.bind_mut_ref("separated", true, &mut separated, "");
+
+            let result = flag_set.parse(test.args.iter().map(|a| a.to_string()));
+
+            match test.expected {
+                Some(expected) => {
+                    assert!(result.is_ok());
+                    assert_eq!(expected, (delimiter.as_str(), fields.as_str(), separated));
+                }
+                None => assert!(result.is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_non_ascii() {
+        let mut flag_set = FlagSet::default();
+        let mut value = String::new();
+        flag_set.bind_mut_ref("ärg", true, &mut value, "");
+        let mut verbose = false;
+        flag_set.bind_mut_ref("verbose", true, &mut verbose, "");
+
+        let result = flag_set.parse(["-é"].map(String::from));
+        assert!(matches!(result, Err(FlagError::UnknownFlag(name)) if name == "é"));
+
+        let result = flag_set.parse(["-vé"].map(String::from));
+        assert!(matches!(result, Err(FlagError::UnknownFlag(name)) if name == "vé"));
+
+        let result = flag_set.parse(["-vä", "x", "-äy"].map(String::from));
+        assert!(result.is_ok());
+        assert_eq!("y", value);
+    }
+
+    #[test]
+    fn test_usage_default() {
+        let mut flag_set = FlagSet::default();
+        let mut delimiter = String::from("\t");
+        flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "split on this");
+        let mut limit = 3;
+        flag_set.bind_mut_ref("limit", false, &mut limit, "");
+        let mut name = String::new();
+        flag_set.bind_mut_ref("name", false, &mut name, "a name");
+        let fill = Rc::new(RefCell::new(String::from("-")));
+        flag_set.bind_ref_cell("fill", false, fill, "pad with this");
+
+        let usage = flag_set.usage();
+
+        assert!(usage.contains("-d, --delimiter  split on this (default: \t)\n"));
+        assert!(usage.contains("--limit          (default: 3)\n"));
+        assert!(usage.contains("--name           a name\n"));
+        assert!(usage.contains("--fill           pad with this (default: -)\n"));
+    }
+
+    #[test]
+    fn test_required() {
+        let tests = vec![
+            (vec!["-d", ",", "-f", "1"], None),
+            (vec!["--fields=2"], None),
+            (vec!["-d", ","], Some("missing required flag: fields")),
+            (vec![], Some("missing required flag: fields")),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut delimiter = String::new();
+            flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "");
+            let mut fields = String::new();
+            flag_set.bind_mut_ref("fields", true, &mut fields, "");
+            flag_set.add_required("fields");
+
+            let result = flag_set.parse(args.into_iter().map(String::from));
+
+            assert_eq!(expected, result.err().map(|err| err.to_string()).as_deref());
+        }
+    }
+
+    #[test]
+    fn test_help() {
+        let tests = vec![
+            (vec!["--help"], true),
+            (vec!["-d", ",", "-h"], true),
+            (vec!["-d", "-h"], false),
+            (vec!["--", "--help"], false),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut delimiter = String::new();
+            flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "");
+            flag_set.add_help();
+
+            let result = flag_set.parse(args.into_iter().map(String::from));
+
+            assert_eq!(expected, matches!(result, Err(FlagError::HelpRequested)));
+        }
+    }
+
+    #[test]
+    fn test_usage_sorted_and_aligned() {
+        let mut flag_set = FlagSet::default();
+        let mut zero = false;
+        flag_set.bind_mut_ref("zero", true, &mut zero, "end lines with NUL");
+        let mut append = String::new();
+        flag_set.bind_mut_ref("append", false, &mut append, "append this");
+        flag_set.add_help();
+
+        let expected = concat!(
+            "  --append    append this\n",
+            "  -h, --help  print this help\n",
+            "  -z, --zero  end lines with NUL\n",
+        );
+        assert_eq!(expected, flag_set.usage());
+    }
+
+    #[test]
+    fn test_unknown_flag_suggestion() {
+        let tests = vec![
+            ("--delimeter", "unknown flag: delimeter (did you mean: delimiter?)"),
+            ("--delimeter=,", "unknown flag: delimeter (did you mean: delimiter?)"),
+            ("--feilds", "unknown flag: feilds (did you mean: fields?)"),
+            ("--hepl", "unknown flag: hepl (did you mean: help?)"),
+            ("--compression", "unknown flag: compression"),
+            ("-x", "unknown flag: x"),
+        ];
+
+        for (arg, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut delimiter = String::new();
+            flag_set.bind_mut_ref("delimiter", false, &mut delimiter, "");
+            let mut fields = String::new();
+            flag_set.bind_mut_ref("fields", false, &mut fields, "");
+            flag_set.add_help();
+
+            let result = flag_set.parse([arg.to_string()]);
+
+            assert_eq!(Some(expected), result.err().map(|err| err.to_string()).as_deref());
+        }
+    }
+
+    #[test]
+    fn test_long_short_cluster() {
+        let mut flag_set = FlagSet::default();
+        let mut verbose = false;
+        flag_set.bind_mut_ref("verbose", true, &mut verbose, "");
+        let mut delimiter = String::new();
+        flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "");
+
+        let result = flag_set.parse(["--vd,", "--vx"].map(String::from));
+
+        assert!(matches!(result, Err(FlagError::UnknownFlag(name)) if name == "vx"));
+        assert!(verbose);
+        assert_eq!(",", delimiter);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let tests = vec![
+            ("", "", 0),
+            ("abc", "", 3),
+            ("delimeter", "delimiter", 1),
+            ("feilds", "fields", 2),
+            ("kitten", "sitting", 3),
+            ("äb", "ab", 1),
+        ];
+
+        for (a, b, expected) in tests {
+            assert_eq!(expected, edit_distance(a, b));
+        }
+    }
+
+    #[test]
+    fn test_bind_aliases() {
+        let tests = vec![
+            (vec!["--delimiter", ","], ","),
+            (vec!["-d", ";"], ";"),
+            (vec!["--delim=|"], "|"),
+            (vec!["-sd:"], ":"),
+            (vec!["-d", ",", "--delim", ";"], ";"),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut delimiter = String::new();
+            flag_set.bind_aliases(&["delimiter", "d", "delim"], &mut delimiter, "split on this");
+            let mut separated = false;
+            flag_set.bind_mut_ref("separated", true, &mut separated, "");
+
+            let report = flag_set.parse_os(args.into_iter().map(OsString::from)).unwrap();
+
+            assert!(report.set_flags.contains(&String::from("delimiter")));
+            assert_eq!(expected, delimiter);
+        }
+
+        let mut flag_set = FlagSet::default();
+        let mut delimiter = String::new();
+        flag_set.bind_aliases(&["delimiter", "d"], &mut delimiter, "split on this");
+        assert_eq!("  -d, --delimiter  split on this\n", flag_set.usage());
+    }
+
+    #[test]
+    fn test_flag_error_is_error() {
+        fn parse(args: &[&str]) -> Result<Vec<String>, Box<dyn Error>> {
+            let mut flag_set = FlagSet::default();
+            let mut fields = String::new();
+            flag_set.bind_mut_ref("fields", true, &mut fields, "");
+            let remaining = flag_set.parse(args.iter().map(|arg| arg.to_string()))?;
+            Ok(remaining)
+        }
+
+        assert_eq!(vec!["file"], parse(&["-f", "1", "file"]).unwrap());
+
+        let err = parse(&["--unknown"]).unwrap_err();
+        assert!(err.is::<FlagError>());
+        assert_eq!("unknown flag: unknown", err.to_string());
+    }
+
+    #[test]
+    fn test_bind_choice() {
+        let tests = vec![
+            (vec!["--mode", "byte"], Ok("byte")),
+            (vec!["--mode=field"], Ok("field")),
+            (vec![], Ok("char")),
+            (vec!["--mode", "word"], Err("could not parse flag mode err: invalid value word, expected one of char, byte, field")),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut mode = String::from("char");
+            flag_set.bind_choice("mode", &["char", "byte", "field"], &mut mode, "");
+
+            let result = flag_set.parse(args.into_iter().map(String::from));
+
+            match expected {
+                Ok(expected) => {
+                    assert!(result.is_ok());
+                    assert_eq!(expected, mode);
+                }
+                Err(expected) => assert_eq!(expected, result.unwrap_err().to_string()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_negative_value() {
+        let tests = vec![
+            (vec!["-f", "-1"], Some(-1)),
+            (vec!["--field", "-12", "-v"], Some(-12)),
+            (vec!["-vf", "-3"], Some(-3)),
+            (vec!["-f", "--1"], None),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut field = 0;
+            flag_set.bind_mut_ref("field", true, &mut field, "");
+            let mut verbose = false;
+            flag_set.bind_mut_ref("verbose", true, &mut verbose, "");
+
+            let result = flag_set.parse(args.into_iter().map(String::from));
+
+            assert_eq!(expected, result.ok().map(|_| field));
+        }
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        let tests = vec![
+            (vec!["--fi", "1"], Ok(("1", ""))),
+            (vec!["--fi=2"], Ok(("2", ""))),
+            (vec!["--delimiter", ","], Ok(("", ","))),
+            (vec!["--delimiter-", ","], Err("ambiguous flag: delimiter- (could be: delimiter-auto, delimiter-per-file)")),
+            (vec!["--delim", ","], Err("ambiguous flag: delim (could be: delimiter, delimiter-auto, delimiter-per-file)")),
+            (vec!["--x", ","], Err("unknown flag: x")),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut fields = String::new();
+            flag_set.bind_mut_ref("fields", true, &mut fields, "");
+            let mut delimiter = String::new();
+            flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "");
+            let mut auto = String::new();
+            flag_set.bind_mut_ref("delimiter-auto", false, &mut auto, "");
+            let mut per_file = String::new();
+            flag_set.bind_mut_ref("delimiter-per-file", false, &mut per_file, "");
+
+            let result = flag_set.parse(args.into_iter().map(String::from)).map_err(|err| err.to_string());
+
+            match expected {
+                Ok((expected_fields, expected_delimiter)) => {
+                    assert_eq!(Ok(vec![]), result);
+                    assert_eq!(expected_fields, fields);
+                    assert_eq!(expected_delimiter, delimiter);
+                }
+                Err(expected) => assert_eq!(Err(String::from(expected)), result),
             }
         }
     }
+
+    #[test]
+    fn test_print_usage_to() {
+        let mut flag_set = FlagSet::default();
+        let mut delimiter = String::new();
+        flag_set.bind_mut_ref("delimiter", true, &mut delimiter, "split on this");
+        let mut fields = String::new();
+        flag_set.bind_mut_ref("fields", true, &mut fields, "select these fields");
+
+        let mut out = Vec::new();
+        flag_set.print_usage_to(&mut out).unwrap();
+
+        let usage = String::from_utf8(out).unwrap();
+        assert!(usage.contains("-d, --delimiter"));
+        assert!(usage.contains("-f, --fields"));
+        assert_eq!(flag_set.usage(), usage);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_os_remaining_intact() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let invalid = OsString::from_vec(vec![b'f', 0xFF, b'.', b'g', b'z']);
+        let invalid_dash = OsString::from_vec(vec![b'-', 0xFE]);
+        let tests = vec![
+            (vec![OsString::from("--name=x"), invalid.clone()], vec![invalid.clone()]),
+            (vec![OsString::from("-vn"), OsString::from("x"), invalid.clone(), invalid.clone()], vec![invalid.clone(), invalid.clone()]),
+            (vec![OsString::from("-v"), invalid.clone(), invalid_dash.clone()], vec![invalid.clone(), invalid_dash.clone()]),
+            (vec![invalid.clone(), OsString::from("-v")], vec![invalid.clone(), OsString::from("-v")]),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut name = String::new();
+            flag_set.bind_mut_ref("name", true, &mut name, "");
+            let mut verbose = false;
+            flag_set.bind_mut_ref("verbose", true, &mut verbose, "");
+
+            let report = flag_set.parse_os(args).unwrap();
+
+            assert_eq!(expected, report.remaining);
+        }
+    }
+
+    #[test]
+    fn test_bind_count() {
+        let tests = vec![
+            (vec![], 0),
+            (vec!["-v"], 1),
+            (vec!["-vvv"], 3),
+            (vec!["-v", "-v"], 2),
+            (vec!["-vsv", "--verbose"], 3),
+            (vec!["-vv", "+v"], 0),
+            (vec!["--verbose=5"], 5),
+        ];
+
+        for (args, expected) in tests {
+            let mut flag_set = FlagSet::default();
+            let mut verbose = 0;
+            flag_set.bind_count("verbose", &mut verbose, "");
+            let mut separated = false;
+            flag_set.bind_mut_ref("separated", true, &mut separated, "");
+
+            let remaining = flag_set.parse(args.into_iter().map(String::from)).unwrap();
+
+            assert!(remaining.is_empty());
+            assert_eq!(expected, verbose);
+        }
+    }
 }
@@ -1,109 +1,3235 @@
-use std::fmt::Display;
-use std::io::{BufRead, Chain, Read};
-use std::ops::Range;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hasher};
+use std::io;
+use std::io::{BufRead, Write};
+use std::ops::{Range, RangeInclusive};
 use std::str::FromStr;
-use crate::flags::Value;
 
+pub mod decompress;
+pub mod encoding;
 pub mod flags;
+#[cfg(feature = "tokio")]
+pub mod stream;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Mode {
-    Characters(Vec<Range<usize>>),
-    Bytes(Vec<Range<usize>>),
-    Fields(Vec<usize>, char),
+    Characters(Vec<FieldRange>),
+    /// Like `Characters`, counting grapheme clusters as split by `graphemes`.
+    Graphemes(Vec<FieldRange>),
+    Bytes(Vec<FieldRange>),
+    /// Fields split on the delimiter. An empty delimiter makes every character a field, so an
+    /// empty line has no fields.
+    Fields(Vec<FieldRange>, String),
 }
 
+/// An inclusive range of fields numbered from 1, written `N`, `N-M` or `N-` up to the last field.
+/// A bound prefixed with `-` counts from the end of each line, so `-1` is the last field, `-2` the
+/// one before it and `-2-` the last two. GNU cut's `-N` for fields 1 to N is written `1-N`.
+/// Character, grapheme and byte positions use the same ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldRange {
+    pub start: isize,
+    pub end: isize,
+}
+
+impl FieldRange {
+    pub fn new(start: isize, end: isize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn single(field: isize) -> Self {
+        Self::new(field, field)
+    }
+
+    /// Parses a range whose bounds are read by `bound`, e.g. as spreadsheet column letters.
+    pub fn parse_with(s: &str, bound: impl Fn(&str) -> Result<usize, String>) -> Result<Self, String> {
+        let bound = |b: &str| {
+            let (sign, b) = match b.strip_prefix('-') {
+                Some(b) => (-1, b),
+                None => (1, b),
+            };
+            match bound(b)? {
+                0 => Err(format!("invalid field range {s}, fields are numbered from 1")),
+                b => Ok(sign * b as isize),
+            }
+        };
+
+        // The first dash separates the bounds unless it makes the start count from the end.
+        let separator = s.char_indices().skip(1).find(|(_, c)| *c == '-').map(|(i, _)| i);
+        match separator.map(|i| (&s[..i], &s[i + 1..])) {
+            None if s == "-" => Err(format!("invalid field range {s}, expected N, N-M, N-, -N or -N-")),
+            None => Ok(Self::single(bound(s)?)),
+            Some((start, "")) => Ok(Self::new(bound(start)?, -1)),
+            Some((start, end)) => match (bound(start)?, bound(end)?) {
+                (start, end) if start.signum() == end.signum() && start > end => Err(format!("invalid decreasing range {s}")),
+                (start, end) => Ok(Self::new(start, end)),
+            },
+        }
+    }
+
+    /// The 1-based positions selected in a line with `field_count` fields. Ranges are clamped to
+    /// the line, a single field outside of it selects nothing.
+    pub fn positions(&self, field_count: usize) -> RangeInclusive<usize> {
+        let count = field_count as isize;
+        let bound = |b: isize| if b > 0 { b } else { count + b + 1 };
+        let (start, end) = (bound(self.start), bound(self.end));
+
+        if self.start == self.end && !(1..=count).contains(&start) {
+            return RangeInclusive::new(1, 0);
+        }
+        start.max(1) as usize..=end.clamp(0, count) as usize
+    }
+
+    /// The 0-based character or byte positions selected in a line of `len` items. Unlike
+    /// `positions` the end is not clamped to the line, so that short lines can be padded.
+    fn resolve(&self, len: usize) -> Range<usize> {
+        let len = len as isize;
+        let bound = |b: isize| if b > 0 { b } else { len + b + 1 };
+        let start = bound(self.start).max(1) as usize - 1;
+        start..(bound(self.end).max(0) as usize).max(start)
+    }
+
+    /// The end of a range counted from the start, `usize::MAX` when it runs to the last field.
+    fn forward_end(&self) -> Option<usize> {
+        match (self.start > 0, self.end) {
+            (true, -1) => Some(usize::MAX),
+            (true, end) if end > 0 => Some(end as usize),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for FieldRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, |b| b.parse::<usize>().map_err(|err| format!("invalid field {b}: {err}")))
+    }
+}
+
+impl Display for FieldRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.start, self.end) {
+            (start, end) if start == end => write!(f, "{start}"),
+            (start, -1) => write!(f, "{start}-"),
+            (start, end) => write!(f, "{start}-{end}"),
+        }
+    }
+}
+
+impl Mode {
+    /// Sorts and deduplicates the selected fields and merges overlapping ranges, so every
+    /// position is emitted once and in input order, like GNU cut does. Ranges counted from the
+    /// end sort after the others and are not merged.
+    pub fn normalize(self) -> Self {
+        match self {
+            Mode::Characters(ranges) => Mode::Characters(merge_field_ranges(ranges)),
+            Mode::Graphemes(ranges) => Mode::Graphemes(merge_field_ranges(ranges)),
+            Mode::Bytes(ranges) => Mode::Bytes(merge_field_ranges(ranges)),
+            Mode::Fields(fields, delimiter) => Mode::Fields(merge_field_ranges(fields), delimiter),
+        }
+    }
+
+    /// Renders the selection as a 1-based list like `1-3,5`.
+    pub fn spec(&self) -> String {
+        let (Mode::Characters(ranges) | Mode::Graphemes(ranges) | Mode::Bytes(ranges) | Mode::Fields(ranges, _)) = self;
+        let mut runs: Vec<FieldRange> = Vec::new();
+        for range in ranges {
+            match (runs.last_mut(), range.forward_end()) {
+                (Some(last), Some(_)) if last.end > 0 && last.end + 1 == range.start => last.end = range.end,
+                _ => runs.push(*range),
+            }
+        }
+        runs.iter()
+            .map(FieldRange::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+fn merge_field_ranges(fields: Vec<FieldRange>) -> Vec<FieldRange> {
+    let (mut forward, mut backward): (Vec<_>, Vec<_>) = fields.into_iter()
+        .partition(|field| field.forward_end().is_some());
+
+    forward.sort_unstable_by_key(|field| (field.start, field.forward_end()));
+    let mut merged: Vec<FieldRange> = Vec::new();
+    for field in forward {
+        match merged.last_mut() {
+            Some(last) if field.start as usize <= last.forward_end().unwrap_or(0).saturating_add(1) => {
+                if field.forward_end() > last.forward_end() {
+                    last.end = field.end;
+                }
+            }
+            _ => merged.push(field),
+        }
+    }
+
+    backward.sort_unstable_by_key(|field| (field.start, field.end));
+    backward.dedup();
+    merged.extend(backward);
+    merged
+}
+
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_unstable_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CutStats {
+    pub lines_read: usize,
+    pub lines_emitted: usize,
+    pub lines_suppressed: usize,
+    pub max_field_count: usize,
+}
+
+/// Selects sub-field `index` of field `field` after splitting it on `separator`, written `N:SEP:M`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubField {
+    pub field: usize,
+    pub separator: String,
+    pub index: usize,
+}
+
+impl FromStr for SubField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, rest) = s.split_once(':').ok_or_else(|| format!("invalid sub-field {s}, expected N:SEP:M"))?;
+        let (separator, index) = rest.rsplit_once(':').ok_or_else(|| format!("invalid sub-field {s}, expected N:SEP:M"))?;
+
+        let field = field.parse::<usize>().map_err(|err| format!("invalid sub-field field {field}: {err}"))?;
+        let index = index.parse::<usize>().map_err(|err| format!("invalid sub-field index {index}: {err}"))?;
+        if field == 0 || index == 0 {
+            return Err(format!("invalid sub-field {s}, fields are numbered from 1"));
+        }
+        if separator.is_empty() {
+            return Err(format!("invalid sub-field {s}, separator must not be empty"));
+        }
+
+        Ok(Self { field, separator: separator.to_string(), index })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Pads field `field` to `width` characters, written `N:WIDTH`, `N:0WIDTH` to zero-fill or
+/// `N:<WIDTH` / `N:>WIDTH` to choose the alignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldFormat {
+    pub field: usize,
+    pub align: Align,
+    pub fill: char,
+    pub width: usize,
+}
+
+impl FieldFormat {
+    pub fn apply(&self, value: &str) -> String {
+        let len = value.chars().count();
+        if len >= self.width {
+            return value.to_string();
+        }
+
+        let padding = self.fill.to_string().repeat(self.width - len);
+        match self.align {
+            Align::Left => format!("{value}{padding}"),
+            Align::Right if self.fill == '0' => match value.strip_prefix(['-', '+']) {
+                Some(digits) => format!("{}{padding}{digits}", &value[..1]),
+                None => format!("{padding}{value}"),
+            },
+            Align::Right => format!("{padding}{value}"),
+        }
+    }
+}
+
+impl FromStr for FieldFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, spec) = s.split_once(':').ok_or_else(|| format!("invalid format {s}, expected N:WIDTH"))?;
+        let field = field.parse::<usize>().map_err(|err| format!("invalid format field {field}: {err}"))?;
+        if field == 0 {
+            return Err(format!("invalid format {s}, fields are numbered from 1"));
+        }
+
+        let (align, spec) = match spec.strip_prefix('<') {
+            Some(spec) => (Align::Left, spec),
+            None => (Align::Right, spec.strip_prefix('>').unwrap_or(spec)),
+        };
+        let (fill, width) = match spec.strip_prefix('0') {
+            Some(width) if !width.is_empty() => ('0', width),
+            _ => (' ', spec),
+        };
+        let width = width.parse::<usize>().map_err(|err| format!("invalid format width {width}: {err}"))?;
+
+        Ok(Self { field, align, fill, width })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    #[default]
+    Characters,
+    Bytes,
+}
+
+impl LengthUnit {
+    pub fn len(&self, s: &str) -> usize {
+        match self {
+            LengthUnit::Characters => s.chars().count(),
+            LengthUnit::Bytes => s.len(),
+        }
+    }
+}
+
+impl FromStr for LengthUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chars" | "characters" => Ok(LengthUnit::Characters),
+            "bytes" => Ok(LengthUnit::Bytes),
+            _ => Err(format!("unsupported length unit {s}, expected chars or bytes")),
+        }
+    }
+}
+
+impl Display for LengthUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LengthUnit::Characters => write!(f, "chars"),
+            LengthUnit::Bytes => write!(f, "bytes"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// FNV-1a, stable across builds.
+    #[default]
+    Fnv,
+    /// The SipHash of the standard library, which may change between Rust releases.
+    Sip,
+}
+
+impl HashAlgorithm {
+    pub fn hash(&self, bytes: &[u8]) -> u64 {
+        match self {
+            HashAlgorithm::Fnv => bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3)),
+            HashAlgorithm::Sip => {
+                let mut hasher = DefaultHasher::new();
+                hasher.write(bytes);
+                hasher.finish()
+            }
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fnv" => Ok(HashAlgorithm::Fnv),
+            "sip" => Ok(HashAlgorithm::Sip),
+            _ => Err(format!("unsupported hash algorithm {s}, expected fnv or sip")),
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Fnv => write!(f, "fnv"),
+            HashAlgorithm::Sip => write!(f, "sip"),
+        }
+    }
+}
+
+/// What character and byte mode do with lines shorter than the selected ranges.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShortLinePolicy {
+    /// Emits the part of the ranges the line has.
+    #[default]
+    Truncate,
+    Skip,
+    /// Pads every range to its full width.
+    Pad,
+    /// Fails in `cut`, `cut_reader_with_sep` and `cut_bytes`, the other methods truncate.
+    Error,
+}
+
+impl FromStr for ShortLinePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(ShortLinePolicy::Truncate),
+            "skip" => Ok(ShortLinePolicy::Skip),
+            "pad" => Ok(ShortLinePolicy::Pad),
+            "error" => Ok(ShortLinePolicy::Error),
+            _ => Err(format!("unsupported short line policy {s}, expected one of truncate, skip, pad, error")),
+        }
+    }
+}
+
+impl Display for ShortLinePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShortLinePolicy::Truncate => write!(f, "truncate"),
+            ShortLinePolicy::Skip => write!(f, "skip"),
+            ShortLinePolicy::Pad => write!(f, "pad"),
+            ShortLinePolicy::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// How `Cutter::cut_map` treats a key that appears on several lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    #[default]
+    LastWins,
+    Error,
+}
+
+/// Selects `fields` on lines that have exactly `field_count` fields, written `COUNT:LIST`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionalSpec {
+    pub field_count: usize,
+    pub fields: Vec<FieldRange>,
+}
+
+impl FromStr for ConditionalSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field_count, fields) = s.split_once(':').ok_or_else(|| format!("invalid spec {s}, expected COUNT:LIST"))?;
+        let field_count = field_count.parse::<usize>().map_err(|err| format!("invalid spec field count {field_count}: {err}"))?;
+        let fields = fields.split(',')
+            .map(FieldRange::from_str)
+            .collect::<Result<_, _>>()?;
 
+        Ok(Self { field_count, fields })
+    }
+}
+
+#[derive(Clone)]
 pub struct Cutter {
     mode: Mode,
+    max_fields: Option<usize>,
+    subfield: Option<SubField>,
+    formats: Vec<FieldFormat>,
+    lengths: Option<LengthUnit>,
+    count_only: bool,
+    excluded_names: Vec<String>,
+    field_names: Vec<String>,
+    conditional_specs: Vec<ConditionalSpec>,
+    drop_unmatched: bool,
+    csv: bool,
+    strip_quotes: bool,
+    hash: Option<(HashAlgorithm, usize)>,
+    numeric_filter: Option<(usize, bool)>,
+    line_length: Option<(RangeInclusive<usize>, LengthUnit)>,
+    annotate: bool,
+    skip_empty: bool,
+    short_lines: ShortLinePolicy,
+    pad: char,
+    output_delimiter: Option<String>,
+    complement: bool,
+    only_delimited: bool,
+    terminator: u8,
+    no_split_chars: bool,
+    whitespace: bool,
+    strip_cr: bool,
+    append_delimiter: bool,
+    json: bool,
+    tab_width: Option<usize>,
+    lines: Vec<Range<usize>>,
 }
 
 impl Cutter {
     pub fn new(mode: Mode) -> Self {
-        Self { mode }
+        Self {
+            mode,
+            max_fields: None,
+            subfield: None,
+            formats: Vec::new(),
+            lengths: None,
+            count_only: false,
+            excluded_names: Vec::new(),
+            field_names: Vec::new(),
+            conditional_specs: Vec::new(),
+            drop_unmatched: false,
+            csv: false,
+            strip_quotes: false,
+            hash: None,
+            numeric_filter: None,
+            line_length: None,
+            annotate: false,
+            skip_empty: false,
+            short_lines: ShortLinePolicy::default(),
+            pad: ' ',
+            output_delimiter: None,
+            complement: false,
+            only_delimited: false,
+            terminator: b'\n',
+            no_split_chars: false,
+            whitespace: false,
+            strip_cr: true,
+            append_delimiter: false,
+            json: false,
+            tab_width: None,
+            lines: Vec::new(),
+        }
     }
 
-    pub fn cut(&self, reader: impl BufRead) -> Vec<String> {
-        let mut result = Vec::new();
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
 
-        for line in reader.lines() {
-            let remaining = self.filter(&line.unwrap());
-            result.push(remaining);
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Replaces the delimiter in field mode, other modes are left unchanged.
+    pub fn with_delimiter(mut self, delimiter: String) -> Self {
+        if let Mode::Fields(_, current) = &mut self.mode {
+            *current = delimiter;
+        }
+        self
+    }
+
+    pub fn is_byte_mode(&self) -> bool {
+        matches!(self.mode, Mode::Bytes(_))
+    }
+
+    /// Joins the selected items with `delimiter` instead of the input delimiter, or instead of
+    /// nothing in character and byte mode.
+    pub fn with_output_delimiter(mut self, delimiter: String) -> Self {
+        self.output_delimiter = Some(delimiter);
+        self
+    }
+
+    /// Ends every non-empty selection with the separator, so each record ends in a delimiter.
+    pub fn with_append_delimiter(mut self, append_delimiter: bool) -> Self {
+        self.append_delimiter = append_delimiter;
+        self
+    }
+
+    /// The separator put between selected items: the delimiter in field mode, nothing otherwise.
+    fn separator(&self) -> String {
+        match (&self.output_delimiter, &self.mode) {
+            (Some(delimiter), _) => delimiter.clone(),
+            (None, Mode::Fields(..)) if self.whitespace => String::from(" "),
+            (None, Mode::Fields(_, delimiter)) => delimiter.clone(),
+            (None, _) => String::new(),
+        }
+    }
+
+    /// Chains `next` after this cutter, see [`Pipeline`].
+    pub fn then(self, next: Cutter) -> Pipeline {
+        Pipeline { stages: vec![self, next] }
+    }
+
+    /// Caps the number of fields split out of a single line, fields beyond the limit are dropped
+    /// without splitting the rest of the line.
+    pub fn with_max_fields(mut self, max_fields: usize) -> Self {
+        self.max_fields = Some(max_fields);
+        self
+    }
+
+    pub fn with_subfield(mut self, subfield: SubField) -> Self {
+        self.subfield = Some(subfield);
+        self
+    }
+
+    pub fn with_format(mut self, format: FieldFormat) -> Self {
+        self.formats.push(format);
+        self
+    }
+
+    /// Emits the length of each selected item instead of its content.
+    pub fn with_lengths(mut self, unit: LengthUnit) -> Self {
+        self.lengths = Some(unit);
+        self
+    }
+
+    /// Treats the first line as a header and selects every field whose header is not in `names`.
+    /// Only applies to field mode, the header line itself is cut like any other line.
+    pub fn with_excluded_names(mut self, names: Vec<String>) -> Self {
+        self.excluded_names = names;
+        self
+    }
+
+    /// Treats the first line as a header and selects the fields whose header is in `names`, in the
+    /// order of `names`. Only applies to field mode, the header line itself is cut like any other
+    /// line.
+    pub fn with_field_names(mut self, names: Vec<String>) -> Self {
+        self.field_names = names;
+        self
+    }
+
+    /// Fails if one of the names given to `with_field_names` is not a column of `header`.
+    fn check_field_names(&self, header: &str) -> io::Result<()> {
+        if self.field_names.is_empty() || !matches!(self.mode, Mode::Fields(..)) {
+            return Ok(());
+        }
+
+        let columns = self.columns(header);
+        match self.field_names.iter().find(|name| !columns.iter().any(|column| column == name.as_str())) {
+            Some(name) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown column {name}, available columns: {}", columns.join(", ")),
+            )),
+            None => Ok(()),
         }
+    }
 
-        result
+    /// Returns the excluded names that do not appear in `header`.
+    pub fn missing_names(&self, header: &str) -> Vec<String> {
+        let columns = self.columns(header);
+        self.excluded_names.iter()
+            .filter(|name| !columns.iter().any(|column| column == name.as_str()))
+            .cloned()
+            .collect()
     }
 
-    fn filter(&self, line: &str) -> String {
+    /// Splits a header line into its column names the way lines are split into fields.
+    pub fn columns<'l>(&self, header: &'l str) -> Vec<Cow<'l, str>> {
         match &self.mode {
-            Mode::Characters(ranges) => {
-                let mut output = String::new();
-                let chars = line.chars().collect::<Vec<_>>();
+            Mode::Fields(_, delimiter) => self.split(header, delimiter),
+            _ => vec![Cow::Borrowed(header)],
+        }
+    }
 
-                for range in ranges {
-                    let range = range.clone();
+    /// Splits `line` into at most `with_max_fields` fields, without looking past the last one.
+    /// The byte ranges of the fields `split` finds in `line`, quotes included in CSV mode.
+    fn field_spans(&self, line: &str, delimiter: &str) -> Vec<Range<usize>> {
+        if self.csv && !self.whitespace {
+            return csv_spans(line, delimiter, self.max_fields.unwrap_or(usize::MAX));
+        }
 
-                    if let Some(chars) = chars.get(range) {
-                        output += " ";
-                        let chars = chars.iter().collect::<String>();
-                        output += chars.as_str();
-                    }
-                }
+        self.split(line, delimiter).iter()
+            .map(|field| {
+                let start = field.as_ptr() as usize - line.as_ptr() as usize;
+                start..start + field.len()
+            })
+            .collect()
+    }
 
-                output
-            }
-            Mode::Bytes(ranges) => {
-                let mut output = String::new();
-                let bytes = line.bytes().collect::<Vec<_>>();
+    fn split<'l>(&self, line: &'l str, delimiter: &str) -> Vec<Cow<'l, str>> {
+        let limit = self.max_fields.unwrap_or(usize::MAX);
+        match (self.whitespace, self.csv) {
+            (true, _) => line.split_whitespace().take(limit).map(Cow::Borrowed).collect(),
+            (false, true) => split_csv(line, delimiter, limit).into_iter().map(Cow::Owned).collect(),
+            (false, false) if delimiter.is_empty() => line.char_indices()
+                .take(limit)
+                .map(|(i, c)| Cow::Borrowed(&line[i..i + c.len_utf8()]))
+                .collect(),
+            (false, false) => line.split(delimiter).take(limit).map(Cow::Borrowed).collect(),
+        }
+    }
 
-                for range in ranges {
-                    let range = range.clone();
+    /// The cutter selecting the fields named by `header`, failing if a name given to
+    /// `with_field_names` is missing from it.
+    fn for_header(&self, header: Option<&str>) -> io::Result<Cow<'_, Cutter>> {
+        let (Mode::Fields(_, delimiter), Some(header)) = (&self.mode, header) else {
+            return Ok(Cow::Borrowed(self));
+        };
+        if self.excluded_names.is_empty() && self.field_names.is_empty() {
+            return Ok(Cow::Borrowed(self));
+        }
+        self.check_field_names(header)?;
 
-                    if let Some(bytes) = bytes.get(range) {
-                        output += " ";
-                        let bytes = String::from_utf8_lossy(bytes);
-                        output += &bytes;
-                    }
-                }
+        let columns = self.columns(header);
+        let fields = match self.field_names.is_empty() {
+            true => columns.iter()
+                .enumerate()
+                .filter(|(_, column)| !self.excluded_names.iter().any(|name| name == column.as_ref()))
+                .map(|(i, _)| FieldRange::single(i as isize + 1))
+                .collect(),
+            false => self.field_names.iter()
+                .filter_map(|name| columns.iter().position(|column| column == name.as_str()))
+                .map(|i| FieldRange::single(i as isize + 1))
+                .collect(),
+        };
 
-                output
-            }
-            Mode::Fields(arg_list, delimiter) => {
-                let fields = line.split(*delimiter).collect::<Vec<_>>();
+        Ok(Cow::Owned(Cutter {
+            mode: Mode::Fields(fields, delimiter.clone()),
+            excluded_names: Vec::new(),
+            field_names: Vec::new(),
+            ..self.clone()
+        }))
+    }
 
-                let mut output = String::new();
-                for i in arg_list.iter() {
-                    if let Some(field) = fields.get(*i - 1) {
-                        output += " ";
-                        output += field;
-                    }
+    /// Uses the fields of the first spec matching a line's field count instead of the mode's.
+    /// Lines matching no spec are passed through unchanged, or dropped with `drop_unmatched`.
+    pub fn with_conditional_specs(mut self, specs: Vec<ConditionalSpec>, drop_unmatched: bool) -> Self {
+        self.conditional_specs = specs;
+        self.drop_unmatched = drop_unmatched;
+        self
+    }
+
+    /// Emits the number of selected items of each line instead of the items. The count is a bare
+    /// number even with `with_json`.
+    pub fn with_count_only(mut self, count_only: bool) -> Self {
+        self.count_only = count_only;
+        self
+    }
+
+    /// Splits fields like CSV: delimiters inside double quotes do not split, the quotes are
+    /// removed and `""` inside a quoted field stands for one quote. Records spanning several
+    /// lines are not supported.
+    pub fn with_csv(mut self, csv: bool) -> Self {
+        self.csv = csv;
+        self
+    }
+
+    /// Splits fields on runs of whitespace like awk, ignoring leading and trailing whitespace. The
+    /// selected fields are joined with a single space unless an output delimiter is set.
+    pub fn with_whitespace(mut self, whitespace: bool) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Removes one pair of surrounding double quotes from every selected field.
+    pub fn with_strip_quotes(mut self, strip_quotes: bool) -> Self {
+        self.strip_quotes = strip_quotes;
+        self
+    }
+
+    /// Prepends the first `length` hex digits of the hash of each output line, so identical
+    /// selections get identical prefixes.
+    pub fn with_hash(mut self, algorithm: HashAlgorithm, length: usize) -> Self {
+        self.hash = Some((algorithm, length.clamp(1, 16)));
+        self
+    }
+
+    /// Keeps only lines whose `field` parses as a number, or only those where it does not if
+    /// `numeric` is false. A missing field is not numeric.
+    pub fn with_numeric_filter(mut self, field: usize, numeric: bool) -> Self {
+        self.numeric_filter = Some((field, numeric));
+        self
+    }
+
+    /// Drops lines whose length, measured in `unit`, is outside of `range`.
+    pub fn with_line_length(mut self, range: RangeInclusive<usize>, unit: LengthUnit) -> Self {
+        self.line_length = Some((range, unit));
+        self
+    }
+
+    /// Emits whole lines with the selected items wrapped in `[` and `]` instead of the items. Takes
+    /// precedence over `with_count_only` and `with_json`.
+    pub fn with_annotate(mut self, annotate: bool) -> Self {
+        self.annotate = annotate;
+        self
+    }
+
+    /// Drops lines whose selection is empty instead of emitting a blank line for them.
+    pub fn with_skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Sets how character and byte mode treat lines shorter than the selection, `pad` is used by
+    /// `ShortLinePolicy::Pad`.
+    pub fn with_short_line_policy(mut self, policy: ShortLinePolicy, pad: char) -> Self {
+        self.short_lines = policy;
+        self.pad = pad;
+        self
+    }
+
+    /// Inverts the selection, keeping every field, character or byte the mode does not list.
+    pub fn with_complement(mut self, complement: bool) -> Self {
+        self.complement = complement;
+        self
+    }
+
+    /// Drops lines without a delimiter in field mode instead of passing them through unchanged.
+    pub fn with_only_delimited(mut self, only_delimited: bool) -> Self {
+        self.only_delimited = only_delimited;
+        self
+    }
+
+    /// Keeps byte mode from emitting parts of multi-byte characters.
+    pub fn with_no_split_chars(mut self, no_split_chars: bool) -> Self {
+        self.no_split_chars = no_split_chars;
+        self
+    }
+
+    /// Sets the byte that ends lines in `cut` and `cut_to_writer`, e.g. `b'\0'` for the output of
+    /// `find -print0`.
+    pub fn with_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Whether the `\r` of CRLF line endings is removed before cutting, on by default. Byte mode
+    /// always keeps it.
+    pub fn with_strip_cr(mut self, strip_cr: bool) -> Self {
+        self.strip_cr = strip_cr;
+        self
+    }
+
+    /// Prints the selection of each line as a JSON array of strings, e.g. `["a","c"]`.
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Expands tabs to spaces up to the next multiple of `tab_width` columns before cutting
+    /// characters or graphemes, like `expand`. Other modes are left unchanged.
+    pub fn with_expand_tabs(mut self, tab_width: usize) -> Self {
+        self.tab_width = Some(tab_width.max(1));
+        self
+    }
+
+    fn expand_tabs<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        let (Mode::Characters(_) | Mode::Graphemes(_), Some(tab_width)) = (&self.mode, self.tab_width) else {
+            return Cow::Borrowed(line);
+        };
+        if !line.contains('\t') {
+            return Cow::Borrowed(line);
+        }
+
+        let mut expanded = String::with_capacity(line.len() + tab_width);
+        let mut column = 0;
+        for c in line.chars() {
+            match c {
+                '\t' => {
+                    let spaces = tab_width - column % tab_width;
+                    expanded.extend(std::iter::repeat(' ').take(spaces));
+                    column += spaces;
+                }
+                c => {
+                    expanded.push(c);
+                    column += 1;
                 }
-                output
             }
         }
+        Cow::Owned(expanded)
     }
-}
 
+    /// Only cuts the lines in `ranges`, numbered from 0 like character ranges, and skips the
+    /// others. An empty list keeps every line.
+    pub fn with_lines(mut self, ranges: Vec<Range<usize>>) -> Self {
+        self.lines = ranges;
+        self
+    }
 
+    /// Whether the 1-based `line` is one of the lines set by `with_lines`.
+    fn selects_line(&self, line: usize) -> bool {
+        self.lines.is_empty() || self.lines.iter().any(|range| range.contains(&(line - 1)))
+    }
 
+    /// Removes the trailing `sep` from a record and, as set by `with_strip_cr`, the `\r` of a CRLF
+    /// line ending.
+    fn trim_record<'r>(&self, record: &'r [u8], sep: u8) -> &'r [u8] {
+        let record = record.strip_suffix(&[sep]).unwrap_or(record);
+        match self.strip_cr && sep == b'\n' && !self.is_byte_mode() {
+            true => record.strip_suffix(b"\r").unwrap_or(record),
+            false => record,
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::fs::File;
-    use std::io::BufReader;
-    use std::path::Path;
-    use super::*;
+    /// Whether a line of `len` characters or bytes ends before one of the selected ranges does.
+    /// A complemented selection never reaches past the line.
+    fn is_short(&self, len: usize) -> bool {
+        match &self.mode {
+            _ if self.complement => false,
+            Mode::Characters(ranges) | Mode::Graphemes(ranges) | Mode::Bytes(ranges) => ranges.iter().any(|range| range.resolve(len).end > len),
+            Mode::Fields(..) => false,
+        }
+    }
 
-    #[test]
-    fn test_field() -> Result<(), String> {
-        let field = vec![2];
-        let cutter = Cutter::new(Mode::Fields(field, '\t'));
-        let path = Path::new("src").join("testdata").join("sample.tsv");
-        let file = File::open(path).map_err(|err| err.to_string())?;
-        let bufReader = BufReader::new(file);
+    /// The character or byte ranges to cut from a line of `len` items. Ranges counted from the
+    /// end are merged with the others, lines shorter than such a range emit what they have.
+    fn ranges(&self, ranges: &[FieldRange], len: usize) -> Vec<Range<usize>> {
+        let resolved = ranges.iter().map(|range| range.resolve(len)).collect::<Vec<_>>();
+        let resolved = match ranges.iter().any(|range| range.forward_end().is_none()) {
+            true => merge_ranges(resolved),
+            false => resolved,
+        };
+        match self.complement {
+            true => complement_ranges(&resolved, len),
+            false => resolved,
+        }
+    }
 
-        let expected = vec!["f1", "1", "6", "11", "16", "21"];
-        let actual = cutter.cut(bufReader);
-        assert_eq!(expected, actual);
-        Ok(())
+    /// Like `ranges`, but moves the bounds of byte ranges off the middle of UTF-8 sequences when
+    /// splitting characters is disabled. A character is kept if its first byte is selected.
+    fn byte_ranges(&self, ranges: &[FieldRange], bytes: &[u8]) -> Vec<Range<usize>> {
+        let ranges = self.ranges(ranges, bytes.len());
+        if !self.no_split_chars {
+            return ranges;
+        }
+
+        let boundary = |mut i: usize| {
+            while bytes.get(i).is_some_and(|byte| byte & 0xC0 == 0x80) {
+                i += 1;
+            }
+            i
+        };
+        ranges.iter().map(|range| boundary(range.start)..boundary(range.end)).collect()
     }
-}
\ No newline at end of file
+
+    /// The 1-based positions of the fields to cut from a line of `field_count` fields.
+    fn positions(&self, fields: &[FieldRange], field_count: usize) -> Vec<usize> {
+        let resolved = resolve_fields(fields, field_count);
+        match self.complement {
+            true => (1..=field_count).filter(|i| !resolved.contains(i)).collect(),
+            false => resolved,
+        }
+    }
+
+    fn check_short_line(&self, record: &[u8], line: usize) -> io::Result<()> {
+        if self.short_lines != ShortLinePolicy::Error {
+            return Ok(());
+        }
+        let len = match &self.mode {
+            Mode::Characters(_) => self.expand_tabs(&String::from_utf8_lossy(record)).chars().count(),
+            Mode::Graphemes(_) => graphemes(&self.expand_tabs(&String::from_utf8_lossy(record))).len(),
+            Mode::Bytes(_) => record.len(),
+            Mode::Fields(..) => return Ok(()),
+        };
+        match self.is_short(len) {
+            true => Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {line} is shorter than the selection"))),
+            false => Ok(()),
+        }
+    }
+
+    fn digest(&self, output: &[u8]) -> Option<String> {
+        let (algorithm, length) = self.hash?;
+        let mut digest = format!("{:016x}", algorithm.hash(output));
+        digest.truncate(length);
+        Some(digest)
+    }
+
+    /// Cuts every line of `reader`. Fails on the first read error, and on the first short line
+    /// under `ShortLinePolicy::Error`.
+    pub fn cut(&self, reader: impl BufRead) -> io::Result<Vec<String>> {
+        self.cut_with_stats(reader).map(|(result, _)| result)
+    }
+
+    pub fn cut_with_stats(&self, reader: impl BufRead) -> io::Result<(Vec<String>, CutStats)> {
+        let mut result = Vec::new();
+        let stats = self.cut_each(reader, |output| {
+            result.push(output.to_string());
+            Ok(())
+        })?;
+        Ok((result, stats))
+    }
+
+    /// Like `cut`, but writes every selection to `writer` as soon as its line is read instead of
+    /// collecting the output.
+    pub fn cut_to_writer(&self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        self.cut_each(reader, |output| {
+            writer.write_all(output.as_bytes())?;
+            writer.write_all(&[self.terminator])
+        })?;
+        Ok(())
+    }
+
+    /// Passes the selection of every line of `reader` to `emit`, reading all lines into one buffer,
+    /// decoding their characters into another and building the output in a third.
+    fn cut_each(&self, mut reader: impl BufRead, mut emit: impl FnMut(&str) -> io::Result<()>) -> io::Result<CutStats> {
+        let mut stats = CutStats::default();
+        let mut buffer = Vec::new();
+        let mut chars = Vec::new();
+        let mut output = String::new();
+        let mut cutter = None;
+
+        loop {
+            buffer.clear();
+            if reader.read_until(self.terminator, &mut buffer)? == 0 {
+                break;
+            }
+            let line = self.trim_record(&buffer, self.terminator);
+            let line = std::str::from_utf8(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let cutter = match &mut cutter {
+                Some(cutter) => cutter,
+                header @ None => header.insert(self.for_header(Some(line))?),
+            };
+
+            stats.lines_read += 1;
+            if !cutter.selects_line(stats.lines_read) {
+                continue;
+            }
+            cutter.check_short_line(line.as_bytes(), stats.lines_read)?;
+
+            let mut field_count = None;
+            let kept = cutter.filter_into(line, &mut chars, &mut field_count, &mut output);
+            let field_count = field_count.unwrap_or_else(|| cutter.count_fields(line));
+            stats.max_field_count = stats.max_field_count.max(field_count);
+            if kept {
+                stats.lines_emitted += 1;
+                emit(&output)?;
+            }
+        }
+
+        stats.lines_suppressed = stats.lines_read - stats.lines_emitted;
+        Ok(stats)
+    }
+
+    fn records<R: BufRead>(&self, reader: R) -> Records<'_, R> {
+        Records { cutter: self, reader }
+    }
+
+    /// Counts how many lines have each number of fields, without cutting anything. Still fails on
+    /// names given to `with_field_names` that the header lacks.
+    pub fn field_count_histogram(&self, reader: impl BufRead) -> io::Result<HashMap<usize, usize>> {
+        let mut histogram = HashMap::new();
+
+        for (i, line) in self.records(reader).enumerate() {
+            let line = line?;
+            if i == 0 {
+                self.check_field_names(&line)?;
+            }
+            *histogram.entry(self.count_fields(&line)).or_default() += 1;
+        }
+
+        Ok(histogram)
+    }
+
+    fn count_fields(&self, line: &str) -> usize {
+        let limit = self.max_fields.unwrap_or(usize::MAX);
+        match &self.mode {
+            Mode::Characters(_) => line.chars().count(),
+            Mode::Graphemes(_) => graphemes(line).len(),
+            Mode::Bytes(_) => line.len(),
+            Mode::Fields(..) if self.whitespace => line.split_whitespace().take(limit).count(),
+            Mode::Fields(_, delimiter) if self.csv => split_csv(line, delimiter, limit).len(),
+            Mode::Fields(_, delimiter) if delimiter.is_empty() => line.chars().take(limit).count(),
+            Mode::Fields(_, delimiter) => line.split(delimiter.as_str()).take(limit).count(),
+        }
+    }
+
+    /// Cuts `reader` lazily, one line at a time.
+    ///
+    /// ```
+    /// use cccut::{Cutter, FieldRange, Mode};
+    ///
+    /// let cutter = Cutter::new(Mode::Fields(vec![FieldRange::single(2)], String::from(",")));
+    /// let mut names = Vec::new();
+    /// for line in cutter.cut_lines("1,ann\n2,bob\n".as_bytes()) {
+    ///     names.push(line?);
+    /// }
+    /// assert_eq!(vec!["ann", "bob"], names);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn cut_lines<R: BufRead>(&self, reader: R) -> CutIter<'_, R> {
+        CutIter {
+            cutter: Cow::Borrowed(self),
+            records: self.records(reader),
+            line: 0,
+        }
+    }
+
+    #[deprecated(note = "use `cut_lines`")]
+    pub fn lines<R: BufRead>(&self, reader: R) -> CutIter<'_, R> {
+        self.cut_lines(reader)
+    }
+
+    /// Like `cut`, but splits the input into records on `sep` instead of newlines.
+    pub fn cut_reader_with_sep(&self, reader: impl BufRead, sep: u8) -> io::Result<Vec<String>> {
+        let mut result = Vec::new();
+
+        let mut records = reader.split(sep)
+            .map(|record| {
+                let mut record = record?;
+                record.truncate(self.trim_record(&record, sep).len());
+                String::from_utf8(record).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            })
+            .peekable();
+        let header = match records.peek() {
+            Some(Ok(header)) => Some(header.as_str()),
+            _ => None,
+        };
+        let cutter = self.for_header(header)?;
+
+        for (i, record) in records.enumerate() {
+            let record = record?;
+            if !cutter.selects_line(i + 1) {
+                continue;
+            }
+            cutter.check_short_line(record.as_bytes(), i + 1)?;
+            result.extend(cutter.filter(&record));
+        }
+
+        Ok(result)
+    }
+
+    /// Writes the selection of every record to `writer`, terminating each with `sep`. In byte mode
+    /// the selected bytes are copied verbatim, so non UTF-8 input survives unchanged. Returns the
+    /// number of bytes written, not counting the record separators.
+    pub fn cut_bytes(&self, reader: impl BufRead, mut writer: impl Write, sep: u8) -> io::Result<usize> {
+        let mut written = 0;
+
+        for (i, record) in reader.split(sep).enumerate() {
+            let record = record?;
+            let record = self.trim_record(&record, sep);
+            if !self.selects_line(i + 1) {
+                continue;
+            }
+            self.check_short_line(record, i + 1)?;
+            if let Some(output) = self.filter_bytes(record) {
+                written += output.len();
+                writer.write_all(&output)?;
+                writer.write_all(&[sep])?;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Byte-level counterpart of `filter`, other modes and options that look at the text of the
+    /// line decode the record lossily first.
+    pub fn filter_bytes(&self, record: &[u8]) -> Option<Vec<u8>> {
+        let (Mode::Bytes(ranges), true) = (&self.mode, self.is_raw_bytes()) else {
+            return self.filter(&String::from_utf8_lossy(record)).map(String::into_bytes);
+        };
+
+        if self.short_lines == ShortLinePolicy::Skip && self.is_short(record.len()) {
+            return None;
+        }
+
+        let separator = self.separator();
+        let mut items = Vec::new();
+        for range in self.byte_ranges(ranges, record).iter() {
+            let mut bytes = record[clamp(range, record.len())].to_vec();
+            if self.short_lines == ShortLinePolicy::Pad {
+                let missing = range.len() - bytes.len();
+                bytes.extend(self.pad.to_string().repeat(missing).bytes());
+            }
+            if !bytes.is_empty() {
+                items.push(bytes);
+            }
+        }
+        let mut output = items.join(separator.as_bytes());
+        if self.append_delimiter && !items.is_empty() {
+            output.extend_from_slice(separator.as_bytes());
+        }
+        if output.is_empty() && self.skip_empty {
+            return None;
+        }
+
+        match self.digest(&output) {
+            Some(digest) => Some([digest.as_bytes(), b" ", &output].concat()),
+            None => Some(output),
+        }
+    }
+
+    pub fn cut_transposed(&self, reader: impl BufRead, fill: &str) -> io::Result<Vec<String>> {
+        let lines = self.records(reader).collect::<io::Result<Vec<_>>>()?;
+        let cutter = self.for_header(lines.first().map(String::as_str))?;
+
+        let rows = lines.iter()
+            .enumerate()
+            .filter(|(i, _)| cutter.selects_line(i + 1))
+            .filter_map(|(_, line)| cutter.select(line))
+            .collect();
+
+        Ok(transpose(rows, fill).iter()
+            .map(|row| row.join(&cutter.separator()))
+            .collect())
+    }
+
+    /// Renders the selection as an aligned table, the first line being the header.
+    pub fn cut_table(&self, reader: impl BufRead) -> io::Result<Vec<String>> {
+        let lines = self.records(reader).collect::<io::Result<Vec<_>>>()?;
+        let cutter = self.for_header(lines.first().map(String::as_str))?;
+
+        let rows = lines.iter()
+            .enumerate()
+            .filter(|(i, _)| cutter.selects_line(i + 1))
+            .filter_map(|(_, line)| cutter.select(line))
+            .collect();
+
+        Ok(render_table(rows))
+    }
+
+    /// Groups consecutive lines sharing the same `key_field` into one line holding the key and the
+    /// output of each member joined by commas. Unsorted input only groups consecutive runs.
+    pub fn cut_grouped(&self, reader: impl BufRead, key_field: usize) -> io::Result<Vec<String>> {
+        let mut result = Vec::new();
+        let mut group: Option<(String, Vec<String>)> = None;
+
+        let lines = self.records(reader).collect::<io::Result<Vec<_>>>()?;
+        let cutter = self.for_header(lines.first().map(String::as_str))?;
+
+        for (i, line) in lines.iter().enumerate() {
+            if !cutter.selects_line(i + 1) {
+                continue;
+            }
+            let key = cutter.field(line, key_field).unwrap_or_default();
+            let Some(output) = cutter.filter(line) else {
+                continue;
+            };
+
+            match &mut group {
+                Some((current, values)) if *current == key => values.push(output),
+                _ => {
+                    if let Some((key, values)) = group.take() {
+                        result.push(format!("{key} {}", values.join(",")));
+                    }
+                    group = Some((key.to_string(), vec![output]));
+                }
+            }
+        }
+
+        if let Some((key, values)) = group {
+            result.push(format!("{key} {}", values.join(",")));
+        }
+
+        Ok(result)
+    }
+
+    /// Builds a map from `key_field` to `value_field` of every line. Lines lacking one of the two
+    /// fields are skipped. Like the other readers it fails on names given to `with_field_names`
+    /// that the header lacks.
+    pub fn cut_map(&self, reader: impl BufRead, key_field: usize, value_field: usize, duplicates: DuplicateKeys) -> io::Result<HashMap<String, String>> {
+        let mut map = HashMap::new();
+
+        for (i, line) in self.records(reader).enumerate() {
+            let line = line?;
+            if i == 0 {
+                self.check_field_names(&line)?;
+            }
+            let (Some(key), Some(value)) = (self.field(&line, key_field), self.field(&line, value_field)) else {
+                continue;
+            };
+
+            let previous = map.insert(key.to_string(), value.to_string());
+            if previous.is_some() && duplicates == DuplicateKeys::Error {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("duplicate key {key} on line {}", i + 1)));
+            }
+        }
+
+        Ok(map)
+    }
+
+    fn field<'l>(&self, line: &'l str, field: usize) -> Option<Cow<'l, str>> {
+        match &self.mode {
+            Mode::Fields(_, delimiter) => self.split(line, delimiter).into_iter().nth(field.checked_sub(1)?),
+            _ => (field == 1).then_some(Cow::Borrowed(line)),
+        }
+    }
+
+    fn filter(&self, line: &str) -> Option<String> {
+        let mut output = String::new();
+        self.filter_into(line, &mut Vec::new(), &mut None, &mut output).then_some(output)
+    }
+
+    /// Like `filter`, decoding the characters of `line` into `chars` and writing the selection into
+    /// `output`, so that cutting many lines can reuse both buffers. Returns whether the line is kept.
+    /// `field_count` is set to the number of fields whenever the line got split.
+    fn filter_into(&self, line: &str, chars: &mut Vec<char>, field_count: &mut Option<usize>, output: &mut String) -> bool {
+        output.clear();
+        let line = &*self.expand_tabs(line);
+        let Some(selected) = self.select_with(line, chars, field_count) else {
+            return false;
+        };
+        match (self.annotate, self.count_only) {
+            (true, _) => output.push_str(&self.annotated(line)),
+            (false, true) => output.push_str(&selected.len().to_string()),
+            (false, false) if self.json => output.push_str(&json_array(&selected)),
+            (false, false) => {
+                let separator = self.separator();
+                for (i, item) in selected.iter().enumerate() {
+                    if i > 0 {
+                        output.push_str(&separator);
+                    }
+                    output.push_str(item);
+                }
+                if self.append_delimiter && !selected.is_empty() {
+                    output.push_str(&separator);
+                }
+            }
+        }
+        if output.is_empty() && self.skip_empty {
+            return false;
+        }
+        if let Some(digest) = self.digest(output.as_bytes()) {
+            output.insert_str(0, &format!("{digest} "));
+        }
+        true
+    }
+
+    fn is_raw_bytes(&self) -> bool {
+        !self.annotate && !self.json && !self.count_only && self.lengths.is_none() && self.line_length.is_none()
+            && self.numeric_filter.is_none()
+    }
+
+    fn annotated(&self, line: &str) -> String {
+        let annotated = match &self.mode {
+            Mode::Characters(ranges) => {
+                let mut buffer = [0; 4];
+                let chars = line.chars().map(|c| c.encode_utf8(&mut buffer).as_bytes().to_vec()).collect::<Vec<_>>();
+                let ranges = self.ranges(ranges, chars.len());
+                mark(&chars, |i| ranges.iter().any(|range| range.contains(&i)))
+            }
+            Mode::Graphemes(ranges) => {
+                let graphemes = graphemes(line).iter().map(|grapheme| grapheme.as_bytes().to_vec()).collect::<Vec<_>>();
+                let ranges = self.ranges(ranges, graphemes.len());
+                mark(&graphemes, |i| ranges.iter().any(|range| range.contains(&i)))
+            }
+            Mode::Bytes(ranges) => {
+                let bytes = line.bytes().map(|byte| vec![byte]).collect::<Vec<_>>();
+                let ranges = self.byte_ranges(ranges, line.as_bytes());
+                mark(&bytes, |i| ranges.iter().any(|range| range.contains(&i)))
+            }
+            Mode::Fields(arg_list, delimiter) => {
+                let spans = self.field_spans(line, delimiter);
+                let arg_list = match self.conditional_spec(spans.len()) {
+                    Some(spec) => &spec.fields,
+                    None => arg_list,
+                };
+                let mut selected = vec![false; spans.len()];
+                for i in self.positions(arg_list, spans.len()) {
+                    selected[i - 1] = true;
+                }
+                return mark_spans(line, spans.into_iter().zip(selected).filter_map(|(span, selected)| selected.then_some(span)));
+            }
+        };
+        String::from_utf8_lossy(&annotated).into_owned()
+    }
+
+    /// Returns the selected items of `line`, or `None` if the line is dropped.
+    fn select(&self, line: &str) -> Option<Vec<String>> {
+        self.select_with(&self.expand_tabs(line), &mut Vec::new(), &mut None)
+    }
+
+    fn select_with(&self, line: &str, chars: &mut Vec<char>, field_count: &mut Option<usize>) -> Option<Vec<String>> {
+        let mut selected = Vec::new();
+
+        if self.short_lines == ShortLinePolicy::Skip {
+            let len = match &self.mode {
+                Mode::Characters(_) => line.chars().count(),
+                Mode::Graphemes(_) => graphemes(line).len(),
+                _ => line.len(),
+            };
+            if self.is_short(len) {
+                return None;
+            }
+        }
+
+        if let Some((range, unit)) = &self.line_length {
+            if !range.contains(&unit.len(line)) {
+                return None;
+            }
+        }
+
+        if let Some((field, numeric)) = self.numeric_filter {
+            let is_numeric = self.field(line, field).is_some_and(|value| value.parse::<f64>().is_ok());
+            if is_numeric != numeric {
+                return None;
+            }
+        }
+
+        match &self.mode {
+            Mode::Characters(ranges) => {
+                chars.clear();
+                chars.extend(line.chars());
+
+                for range in self.ranges(ranges, chars.len()).iter() {
+                    let mut item = chars[clamp(range, chars.len())].iter().collect::<String>();
+                    if self.short_lines == ShortLinePolicy::Pad {
+                        let missing = range.len() - item.chars().count();
+                        item.extend(std::iter::repeat(self.pad).take(missing));
+                    }
+
+                    if !item.is_empty() {
+                        selected.push(item);
+                    }
+                }
+            }
+            Mode::Graphemes(ranges) => {
+                let graphemes = graphemes(line);
+
+                for range in self.ranges(ranges, graphemes.len()).iter() {
+                    let mut item = graphemes[clamp(range, graphemes.len())].concat();
+                    if self.short_lines == ShortLinePolicy::Pad {
+                        let missing = range.len() - clamp(range, graphemes.len()).len();
+                        item.extend(std::iter::repeat(self.pad).take(missing));
+                    }
+
+                    if !item.is_empty() {
+                        selected.push(item);
+                    }
+                }
+            }
+            Mode::Bytes(ranges) => {
+                let bytes = line.as_bytes();
+
+                for range in self.byte_ranges(ranges, bytes).iter() {
+                    let mut item = String::from_utf8_lossy(&bytes[clamp(range, bytes.len())]).into_owned();
+                    if self.short_lines == ShortLinePolicy::Pad {
+                        let missing = range.len() - clamp(range, bytes.len()).len();
+                        item.extend(std::iter::repeat(self.pad).take(missing));
+                    }
+
+                    if !item.is_empty() {
+                        selected.push(item);
+                    }
+                }
+            }
+            Mode::Fields(arg_list, delimiter) => {
+                let fields = self.split(line, delimiter);
+                *field_count = Some(fields.len());
+                if self.is_undelimited(line, fields.len()) {
+                    return match self.only_delimited {
+                        true => None,
+                        false => Some(vec![line.to_string()]),
+                    };
+                }
+
+                let arg_list = match self.conditional_spec(fields.len()) {
+                    Some(spec) => &spec.fields,
+                    None if self.conditional_specs.is_empty() => arg_list,
+                    None if self.drop_unmatched => return None,
+                    None => return Some(vec![line.to_string()]),
+                };
+
+                for i in self.positions(arg_list, fields.len()) {
+                    if let Some(field) = fields.get(i - 1) {
+                        let value = match &self.subfield {
+                            Some(subfield) if subfield.field == i => {
+                                field.split(subfield.separator.as_str()).nth(subfield.index - 1).unwrap_or_default()
+                            }
+                            _ => field.as_ref(),
+                        };
+                        let value = match self.strip_quotes {
+                            true => strip_quotes(value),
+                            false => value,
+                        };
+
+                        match self.formats.iter().find(|format| format.field == i) {
+                            Some(format) => selected.push(format.apply(value)),
+                            None => selected.push(value.to_string()),
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(self.transform(selected))
+    }
+
+    /// Like `filter`, but returns the selected fields, characters or graphemes as slices of `line`
+    /// instead of joining copies of them. Only the selection is applied, not the options rewriting
+    /// items such as formats, padding or subfields. Returns `None` for undelimited lines `filter`
+    /// drops, and in byte and CSV mode, whose items are not always slices of the line.
+    pub fn select_slices<'l>(&self, line: &'l str) -> Option<Vec<&'l str>> {
+        match &self.mode {
+            Mode::Characters(ranges) => {
+                let offsets = line.char_indices().map(|(i, _)| i).chain([line.len()]).collect::<Vec<_>>();
+                let count = offsets.len() - 1;
+                let slices = self.ranges(ranges, count).iter()
+                    .map(|range| clamp(range, count))
+                    .filter(|range| !range.is_empty())
+                    .map(|range| &line[offsets[range.start]..offsets[range.end]])
+                    .collect();
+                Some(slices)
+            }
+            Mode::Graphemes(ranges) => {
+                let graphemes = graphemes(line);
+                let slices = self.ranges(ranges, graphemes.len()).iter()
+                    .map(|range| clamp(range, graphemes.len()))
+                    .filter(|range| !range.is_empty())
+                    .map(|range| {
+                        let start = graphemes[range.start].as_ptr() as usize - line.as_ptr() as usize;
+                        let end = graphemes[range.end - 1].as_ptr() as usize - line.as_ptr() as usize + graphemes[range.end - 1].len();
+                        &line[start..end]
+                    })
+                    .collect();
+                Some(slices)
+            }
+            Mode::Bytes(_) => None,
+            Mode::Fields(..) if self.csv => None,
+            Mode::Fields(arg_list, delimiter) => {
+                let fields = self.field_spans(line, delimiter).into_iter()
+                    .map(|span| &line[span])
+                    .collect::<Vec<_>>();
+                if self.is_undelimited(line, fields.len()) {
+                    return match self.only_delimited {
+                        true => None,
+                        false => Some(vec![line]),
+                    };
+                }
+                Some(self.positions(arg_list, fields.len()).into_iter().map(|i| fields[i - 1]).collect())
+            }
+        }
+    }
+
+    /// Returns the 1-based positions of the fields, characters or bytes `filter` selects from
+    /// `line`, in output order. Ranges reaching past the end of the line are clamped to it.
+    pub fn selected_indices(&self, line: &str) -> Vec<usize> {
+        let (ranges, len) = match &self.mode {
+            Mode::Characters(ranges) => (self.ranges(ranges, line.chars().count()), line.chars().count()),
+            Mode::Graphemes(ranges) => (self.ranges(ranges, graphemes(line).len()), graphemes(line).len()),
+            Mode::Bytes(ranges) => (self.byte_ranges(ranges, line.as_bytes()), line.len()),
+            Mode::Fields(arg_list, _) => {
+                let field_count = self.count_fields(line);
+                if self.is_undelimited(line, field_count) {
+                    return match self.only_delimited {
+                        true => Vec::new(),
+                        false => vec![1],
+                    };
+                }
+                return match self.conditional_spec(field_count) {
+                    Some(spec) => self.positions(&spec.fields, field_count),
+                    None if self.conditional_specs.is_empty() => self.positions(arg_list, field_count),
+                    None if self.drop_unmatched => Vec::new(),
+                    None => (1..=field_count).collect(),
+                };
+            }
+        };
+
+        ranges.iter()
+            .flat_map(|range| clamp(range, len))
+            .map(|i| i + 1)
+            .collect()
+    }
+
+    /// Whether a line of `field_count` fields has no delimiter, so it is passed through whole or
+    /// dropped with `only_delimited`.
+    fn is_undelimited(&self, line: &str, field_count: usize) -> bool {
+        let undelimited = match (&self.mode, self.whitespace) {
+            (_, true) => !line.contains(char::is_whitespace),
+            (Mode::Fields(_, delimiter), false) if delimiter.is_empty() => false,
+            (_, false) => field_count == 1,
+        };
+        undelimited && self.conditional_spec(field_count).is_none()
+    }
+
+    fn conditional_spec(&self, field_count: usize) -> Option<&ConditionalSpec> {
+        self.conditional_specs.iter().find(|spec| spec.field_count == field_count)
+    }
+
+    fn transform(&self, selected: Vec<String>) -> Vec<String> {
+        match self.lengths {
+            Some(unit) => selected.iter().map(|item| unit.len(item).to_string()).collect(),
+            None => selected,
+        }
+    }
+}
+
+/// Collects the common options of a `Cutter` and checks that they fit together before building it.
+#[derive(Debug, Default, Clone)]
+pub struct CutterBuilder {
+    fields: Option<Vec<FieldRange>>,
+    characters: Option<Vec<FieldRange>>,
+    graphemes: Option<Vec<FieldRange>>,
+    bytes: Option<Vec<FieldRange>>,
+    delimiter: Option<String>,
+    output_delimiter: Option<String>,
+    complement: bool,
+    only_delimited: bool,
+}
+
+impl CutterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fields(mut self, fields: Vec<FieldRange>) -> Self {
+        self.fields = Some(fields);
+        self
+    }
+
+    pub fn characters(mut self, characters: Vec<FieldRange>) -> Self {
+        self.characters = Some(characters);
+        self
+    }
+
+    pub fn graphemes(mut self, graphemes: Vec<FieldRange>) -> Self {
+        self.graphemes = Some(graphemes);
+        self
+    }
+
+    pub fn bytes(mut self, bytes: Vec<FieldRange>) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    /// The input delimiter of field mode, a tab if not set.
+    pub fn delimiter(mut self, delimiter: String) -> Self {
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    pub fn output_delimiter(mut self, delimiter: String) -> Self {
+        self.output_delimiter = Some(delimiter);
+        self
+    }
+
+    pub fn complement(mut self, complement: bool) -> Self {
+        self.complement = complement;
+        self
+    }
+
+    pub fn only_delimited(mut self, only_delimited: bool) -> Self {
+        self.only_delimited = only_delimited;
+        self
+    }
+
+    pub fn build(self) -> Result<Cutter, String> {
+        if self.fields.is_none() && (self.characters.is_some() || self.graphemes.is_some() || self.bytes.is_some()) {
+            if self.delimiter.is_some() {
+                return Err(String::from("an input delimiter may be specified only when operating on fields"));
+            }
+            if self.only_delimited {
+                return Err(String::from("suppressing non-delimited lines makes sense only when operating on fields"));
+            }
+        }
+
+        let mode = match (self.fields, self.characters, self.graphemes, self.bytes) {
+            (Some(fields), None, None, None) => Mode::Fields(fields, self.delimiter.unwrap_or_else(|| String::from("\t"))),
+            (None, Some(characters), None, None) => Mode::Characters(characters),
+            (None, None, Some(graphemes), None) => Mode::Graphemes(graphemes),
+            (None, None, None, Some(bytes)) => Mode::Bytes(bytes),
+            (None, None, None, None) => return Err(String::from("a list of fields, characters, graphemes or bytes is required")),
+            _ => return Err(String::from("only one of fields, characters, graphemes or bytes may be selected")),
+        };
+
+        let mut cutter = Cutter::new(mode)
+            .with_complement(self.complement)
+            .with_only_delimited(self.only_delimited);
+        if let Some(delimiter) = self.output_delimiter {
+            cutter = cutter.with_output_delimiter(delimiter);
+        }
+        Ok(cutter)
+    }
+}
+
+/// Splits `line` into Unicode's extended grapheme clusters.
+#[cfg(feature = "graphemes")]
+fn graphemes(line: &str) -> Vec<&str> {
+    unicode_segmentation::UnicodeSegmentation::graphemes(line, true).collect()
+}
+
+/// Splits `line` into grapheme clusters without the `graphemes` feature. This approximates
+/// Unicode's extended grapheme clusters: combining marks, variation selectors, emoji modifiers and
+/// tags stay with the character before them, zero width joiners also join the character after
+/// them, regional indicators pair up into flags and CRLF is one cluster. Hangul syllables and
+/// Indic spacing marks are not handled.
+#[cfg(not(feature = "graphemes"))]
+fn graphemes(line: &str) -> Vec<&str> {
+    let mut graphemes = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    let mut regional_indicators = 0;
+
+    for (i, c) in line.char_indices() {
+        if let Some(previous) = previous {
+            let joined = is_grapheme_extend(c)
+                || previous == '\u{200D}'
+                || previous == '\r' && c == '\n'
+                || is_regional_indicator(c) && regional_indicators % 2 == 1;
+            if !joined {
+                graphemes.push(&line[start..i]);
+                start = i;
+                regional_indicators = 0;
+            }
+        }
+        if is_regional_indicator(c) {
+            regional_indicators += 1;
+        }
+        previous = Some(c);
+    }
+    if start < line.len() {
+        graphemes.push(&line[start..]);
+    }
+
+    graphemes
+}
+
+#[cfg(not(feature = "graphemes"))]
+fn is_grapheme_extend(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' | '\u{0483}'..='\u{0489}' | '\u{0591}'..='\u{05BD}' | '\u{0610}'..='\u{061A}' |
+        '\u{064B}'..='\u{065F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{200C}'..='\u{200D}' |
+        '\u{20D0}'..='\u{20FF}' | '\u{FE00}'..='\u{FE0F}' | '\u{FE20}'..='\u{FE2F}' | '\u{1F3FB}'..='\u{1F3FF}' |
+        '\u{E0020}'..='\u{E007F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+#[cfg(not(feature = "graphemes"))]
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+fn clamp(range: &Range<usize>, len: usize) -> Range<usize> {
+    range.start.min(len)..range.end.min(len)
+}
+
+/// Returns the gaps `ranges` leave in `0..len`.
+fn complement_ranges(ranges: &[Range<usize>], len: usize) -> Vec<Range<usize>> {
+    let mut gaps = Vec::new();
+    let mut start = 0;
+    for range in merge_ranges(ranges.to_vec()) {
+        let range = clamp(&range, len);
+        if range.start > start {
+            gaps.push(start..range.start);
+        }
+        start = start.max(range.end);
+    }
+    if start < len {
+        gaps.push(start..len);
+    }
+    gaps
+}
+
+/// Concatenates `items`, wrapping each run of adjacent selected items in one pair of brackets.
+fn mark(items: &[Vec<u8>], selected: impl Fn(usize) -> bool) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    for (i, item) in items.iter().enumerate() {
+        let previous = i > 0 && selected(i - 1);
+        if previous && !selected(i) {
+            output.push(b']');
+        }
+        if selected(i) && !previous {
+            output.push(b'[');
+        }
+        output.extend_from_slice(item);
+    }
+    if !items.is_empty() && selected(items.len() - 1) {
+        output.push(b']');
+    }
+
+    output
+}
+
+/// Copies `line`, wrapping the byte ranges `spans` in brackets. Adjacent spans share one pair.
+fn mark_spans(line: &str, spans: impl Iterator<Item=Range<usize>>) -> String {
+    let mut output = String::with_capacity(line.len() + 2);
+    let mut copied = 0;
+
+    for span in spans {
+        match span.start == copied && output.ends_with(']') {
+            true => {
+                output.pop();
+            }
+            false => {
+                output.push_str(&line[copied..span.start]);
+                output.push('[');
+            }
+        }
+        output.push_str(&line[span.clone()]);
+        output.push(']');
+        copied = span.end;
+    }
+    output.push_str(&line[copied..]);
+
+    output
+}
+
+/// Resolves `fields` against a line with `field_count` fields into 1-based positions, dropping
+/// positions outside of the line. A field reached by two different ranges, e.g. from both ends, is
+/// kept once, repeating the same range is not deduplicated.
+fn resolve_fields(fields: &[FieldRange], field_count: usize) -> Vec<usize> {
+    if let [field] = fields {
+        return field.positions(field_count).collect();
+    }
+
+    // The range that first reached each position.
+    let mut claimed: Vec<Option<&FieldRange>> = vec![None; field_count + 1];
+    let mut resolved = Vec::new();
+    for field in fields {
+        for i in field.positions(field_count) {
+            match claimed[i] {
+                Some(other) if other != field => continue,
+                Some(_) => {}
+                None => claimed[i] = Some(field),
+            }
+            resolved.push(i);
+        }
+    }
+    resolved
+}
+
+fn json_array(items: &[String]) -> String {
+    let items = items.iter().map(|item| json_string(item)).collect::<Vec<_>>();
+    format!("[{}]", items.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn strip_quotes(value: &str) -> &str {
+    value.strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Guesses the delimiter of a line as the most frequent of tab, comma, semicolon, pipe and colon,
+/// preferring the earlier one on ties.
+pub fn sniff_delimiter(line: &str) -> Option<char> {
+    ['\t', ',', ';', '|', ':'].into_iter()
+        .rev()
+        .map(|delimiter| (line.matches(delimiter).count(), delimiter))
+        .filter(|(count, _)| *count > 0)
+        .max_by_key(|(count, _)| *count)
+        .map(|(_, delimiter)| delimiter)
+}
+
+/// Splits `line` on `delimiter`, or into single characters if the delimiter is empty.
+/// Splits `line` on `delimiter` outside of double quotes, unquoting quoted fields. Stops after
+/// `limit` fields.
+fn split_csv(line: &str, delimiter: &str, limit: usize) -> Vec<String> {
+    csv_spans(line, delimiter, limit).into_iter()
+        .map(|span| unquote_csv(&line[span]))
+        .collect()
+}
+
+/// The byte ranges of the fields `split_csv` finds in `line`, quotes included.
+fn csv_spans(line: &str, delimiter: &str, limit: usize) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut empty = true;
+    let mut i = 0;
+
+    while let Some(c) = line[i..].chars().next() {
+        if !quoted && !delimiter.is_empty() && line[i..].starts_with(delimiter) {
+            spans.push(start..i);
+            if spans.len() >= limit {
+                return spans;
+            }
+            i += delimiter.len();
+            start = i;
+            empty = true;
+            continue;
+        }
+        i += c.len_utf8();
+
+        match c {
+            '"' if quoted && line[i..].starts_with('"') => {
+                i += 1;
+                empty = false;
+            }
+            '"' if quoted => quoted = false,
+            '"' if empty => quoted = true,
+            _ => empty = false,
+        }
+    }
+    if spans.len() < limit {
+        spans.push(start..line.len());
+    }
+
+    spans
+}
+
+/// Removes the quotes of a field split by `csv_spans`, turning `""` inside quotes into `"`.
+fn unquote_csv(field: &str) -> String {
+    let mut unquoted = String::new();
+    let mut quoted = false;
+    let mut rest = field;
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+
+        match c {
+            '"' if quoted && rest.starts_with('"') => {
+                rest = &rest[1..];
+                unquoted.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if unquoted.is_empty() => quoted = true,
+            c => unquoted.push(c),
+        }
+    }
+
+    unquoted
+}
+
+/// Iterator over the output of a reader returned by `Cutter::cut_lines`. Read errors and short lines
+/// under `ShortLinePolicy::Error` are yielded as errors.
+pub struct CutIter<'c, R> {
+    cutter: Cow<'c, Cutter>,
+    records: Records<'c, R>,
+    line: usize,
+}
+
+impl<R: BufRead> Iterator for CutIter<'_, R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.records.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            self.line += 1;
+
+            if let Some(output) = cut_next(&mut self.cutter, self.line, &line) {
+                return Some(output);
+            }
+        }
+    }
+}
+
+/// Iterator over the records of a reader, split on the terminator of `cutter` and decoded as UTF-8.
+struct Records<'c, R> {
+    cutter: &'c Cutter,
+    reader: R,
+}
+
+impl<R: BufRead> Iterator for Records<'_, R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let terminator = self.cutter.terminator;
+        let mut record = Vec::new();
+        match self.reader.read_until(terminator, &mut record) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(err) => return Some(Err(err)),
+        }
+        record.truncate(self.cutter.trim_record(&record, terminator).len());
+
+        Some(String::from_utf8(record).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)))
+    }
+}
+
+/// Cuts the `number`-th line for the lazy readers, switching to the cutter for the header on the
+/// first line. Returns `None` for lines that are skipped or dropped.
+fn cut_next(cutter: &mut Cow<'_, Cutter>, number: usize, line: &str) -> Option<io::Result<String>> {
+    if number == 1 {
+        match cutter.for_header(Some(line)) {
+            Ok(Cow::Owned(header_cutter)) => *cutter = Cow::Owned(header_cutter),
+            Ok(Cow::Borrowed(_)) => {}
+            Err(err) => return Some(Err(err)),
+        }
+    }
+    if !cutter.selects_line(number) {
+        return None;
+    }
+    if let Err(err) = cutter.check_short_line(line.as_bytes(), number) {
+        return Some(Err(err));
+    }
+
+    cutter.filter(line).map(Ok)
+}
+
+/// Applies several cutters to every line in sequence. The output of a stage is the input line of
+/// the next one, so a field stage following another stage has to split on the separator the
+/// previous stage joins its selection with.
+pub struct Pipeline {
+    stages: Vec<Cutter>,
+}
+
+impl Pipeline {
+    pub fn then(mut self, next: Cutter) -> Self {
+        self.stages.push(next);
+        self
+    }
+
+    pub fn cut(&self, reader: impl BufRead) -> io::Result<Vec<String>> {
+        let mut result = Vec::new();
+
+        for line in reader.lines() {
+            let mut line = Some(line?);
+            for stage in &self.stages {
+                line = line.and_then(|line| stage.filter(&line));
+            }
+            result.extend(line);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Pads every column to its widest cell and puts a rule of dashes below the first row. Short rows
+/// leave their missing cells blank.
+pub fn render_table(rows: Vec<Vec<String>>) -> Vec<String> {
+    let mut widths = Vec::new();
+    for row in &rows {
+        for (column, cell) in row.iter().enumerate() {
+            let width = display_width(cell);
+            match widths.get_mut(column) {
+                Some(max) => *max = width.max(*max),
+                None => widths.push(width),
+            }
+        }
+    }
+
+    let render = |cells: Vec<String>| {
+        let line = widths.iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = cells.get(column).map(String::as_str).unwrap_or_default();
+                format!("{cell}{}", " ".repeat(width - display_width(cell)))
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.trim_end().to_string()
+    };
+
+    let mut table = Vec::new();
+    for (i, row) in rows.into_iter().enumerate() {
+        table.push(render(row));
+        if i == 0 {
+            table.push(render(widths.iter().map(|width| "-".repeat(*width)).collect()));
+        }
+    }
+    table
+}
+
+/// Approximates the number of terminal columns `s` takes, counting East Asian wide characters
+/// and most emoji twice and combining marks not at all.
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| match c as u32 {
+            0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+            0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Turns rows into columns, short rows are padded with `fill`.
+pub fn transpose(rows: Vec<Vec<String>>, fill: &str) -> Vec<Vec<String>> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+    (0..width)
+        .map(|column| rows.iter()
+            .map(|row| row.get(column).cloned().unwrap_or_else(|| fill.to_string()))
+            .collect())
+        .collect()
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+    use std::rc::Rc;
+    use super::*;
+
+    fn fields(fields: &[isize]) -> Vec<FieldRange> {
+        fields.iter().map(|field| FieldRange::single(*field)).collect()
+    }
+
+    /// Turns 0-based half-open ranges into the 1-based ranges of character and byte mode.
+    fn spans(ranges: &[Range<usize>]) -> Vec<FieldRange> {
+        ranges.iter().map(|range| FieldRange::new(range.start as isize + 1, range.end as isize)).collect()
+    }
+
+    #[test]
+    fn test_field() -> Result<(), String> {
+        let cutter = Cutter::new(Mode::Fields(fields(&[2, 4]), String::from("\t")));
+        let path = Path::new("src").join("testdata").join("sample.tsv");
+        let file = File::open(path).map_err(|err| err.to_string())?;
+        let buf_reader = BufReader::new(file);
+
+        let expected = vec!["f1\tf3", "1\t3", "6\t8", "11\t13", "16\t18", "21\t23"];
+        let actual = cutter.cut(buf_reader).unwrap();
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cut_with_stats() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 3]), String::from(",")));
+        let input = "a,b,c\nd,e\nf,g,h,i\n";
+
+        let (output, stats) = cutter.cut_with_stats(input.as_bytes()).unwrap();
+
+        assert_eq!(vec!["a,c", "d", "f,h"], output);
+        assert_eq!(CutStats {
+            lines_read: 3,
+            lines_emitted: 3,
+            lines_suppressed: 0,
+            max_field_count: 4,
+        }, stats);
+
+        let (output, stats) = cutter.with_numeric_filter(1, true).cut_with_stats("1,2\na,b,c,d,e\n".as_bytes()).unwrap();
+
+        assert_eq!(vec!["1"], output);
+        assert_eq!(5, stats.max_field_count);
+    }
+
+    #[test]
+    fn test_resolve_fields() {
+        let tests = vec![
+            (fields(&[1, -1]), 3, vec![1, 3]),
+            (fields(&[1, -1]), 1, vec![1]),
+            (fields(&[1, 1]), 3, vec![1, 1]),
+            (vec![FieldRange::new(2, -1), FieldRange::single(-2)], 3, vec![2, 3]),
+            (fields(&[-3, 2]), 2, vec![2]),
+        ];
+
+        for (fields, field_count, expected) in tests {
+            assert_eq!(expected, resolve_fields(&fields, field_count));
+        }
+
+        let wide = resolve_fields(&[FieldRange::single(1), FieldRange::new(2, -1), FieldRange::single(-1)], 100_000);
+        assert_eq!((1..=100_000).collect::<Vec<_>>(), wide);
+    }
+
+    #[test]
+    fn test_max_fields() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[2, 5]), String::from(","))).with_max_fields(3);
+        let input = format!("a,b{}\n", ",".repeat(10_000));
+
+        let (output, stats) = cutter.cut_with_stats(input.as_bytes()).unwrap();
+
+        assert_eq!(vec!["b"], output);
+        assert_eq!(3, stats.max_field_count);
+
+        let line = format!("a,b,c{}", ",".repeat(10_000));
+        for (cutter, delimiter) in [(cutter.clone(), ","), (cutter.clone().with_csv(true), ","), (cutter.with_delimiter(String::new()), "")] {
+            assert_eq!(3, cutter.split(&line, delimiter).len());
+            assert_eq!(3, cutter.count_fields(&line));
+        }
+
+        let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::new())).with_whitespace(true).with_max_fields(2);
+        assert_eq!(vec!["a", "b"], cutter.split(&format!("a b c{}", " x".repeat(10_000)), ""));
+    }
+
+    #[test]
+    fn test_cut_transposed() {
+        struct TestCase {
+            input: &'static str,
+            fill: &'static str,
+            expected: Vec<&'static str>,
+        }
+        let tests = vec![
+            TestCase {
+                input: "a,b,c,x\nd,e,f,y\n",
+                fill: "",
+                expected: vec!["a,d", "b,e", "c,f"],
+            },
+            TestCase {
+                input: "a,b,c\nd\n",
+                fill: "-",
+                expected: vec!["a,d", "b,-", "c,-"],
+            },
+        ];
+
+        for test in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1, 2, 3]), String::from(",")));
+
+            let actual = cutter.cut_transposed(test.input.as_bytes(), test.fill).unwrap();
+
+            assert_eq!(test.expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_subfield() {
+        let subfield = "2:;:2".parse::<SubField>().unwrap();
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 2]), String::from(","))).with_subfield(subfield);
+        let input = "a,key=value;key2=value2\nb,key=value\n";
+
+        let actual = cutter.cut(input.as_bytes()).unwrap();
+
+        assert_eq!(vec!["a,key2=value2", "b,"], actual);
+    }
+
+    #[test]
+    fn test_parse_subfield() {
+        assert_eq!(Ok(SubField { field: 3, separator: String::from("::"), index: 1 }), "3::::1".parse());
+        assert!("3:;".parse::<SubField>().is_err());
+        assert!("0:;:1".parse::<SubField>().is_err());
+    }
+
+    #[test]
+    fn test_lengths() {
+        let tests = vec![
+            (LengthUnit::Characters, vec!["5,0,2"]),
+            (LengthUnit::Bytes, vec!["6,0,2"]),
+        ];
+
+        for (unit, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1, 2, 3]), String::from(","))).with_lengths(unit);
+
+            assert_eq!(expected, cutter.cut("héllo,,ab\n".as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cut_grouped() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[2, 3]), String::from(",")));
+        let input = "a,1,x\na,2,y\nb,3,z\na,4,w\n";
+
+        let actual = cutter.cut_grouped(input.as_bytes(), 1).unwrap();
+
+        assert_eq!(vec!["a 1,x,2,y", "b 3,z", "a 4,w"], actual);
+    }
+
+    #[test]
+    fn test_multibyte_delimiter() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 3]), String::from("§")));
+
+        assert_eq!(vec!["a§c", "ä"], cutter.cut("a§b§c\nä\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_count_only() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 3, 5]), String::from(","))).with_count_only(true);
+        let input = "a,b,c,d,e\na,b,c\na\n";
+
+        assert_eq!(vec!["3", "2", "1"], cutter.cut(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let tests = vec![
+            (Mode::Fields(fields(&[3, 1, 1, 2]), String::from(",")), Mode::Fields(vec![FieldRange::new(1, 3)], String::from(","))),
+            (Mode::Fields(fields(&[-1, 2, -3, 1]), String::from(",")), Mode::Fields(vec![FieldRange::new(1, 2), FieldRange::single(-3), FieldRange::single(-1)], String::from(","))),
+            (Mode::Fields(vec![FieldRange::new(5, -1), FieldRange::new(1, 2), FieldRange::new(2, 4), FieldRange::single(7)], String::from(",")), Mode::Fields(vec![FieldRange::new(1, -1)], String::from(","))),
+            (Mode::Fields(vec![FieldRange::new(4, 6), FieldRange::new(1, 2)], String::from(",")), Mode::Fields(vec![FieldRange::new(1, 2), FieldRange::new(4, 6)], String::from(","))),
+            (Mode::Characters(spans(&[1..3, 0..2, 5..6, 3..4])), Mode::Characters(spans(&[0..4, 5..6]))),
+            (Mode::Bytes(spans(&[4..6, 0..2])), Mode::Bytes(spans(&[0..2, 4..6]))),
+        ];
+
+        for (mode, expected) in tests {
+            assert_eq!(expected, mode.normalize());
+        }
+    }
+
+    #[test]
+    fn test_spec() {
+        let tests = vec![
+            (Mode::Fields(fields(&[1, 2, 3, 5]), String::from(",")), "1-3,5"),
+            (Mode::Fields(fields(&[3, 1]), String::from(",")), "3,1"),
+            (Mode::Fields(vec![FieldRange::new(1, 2), FieldRange::new(-2, -1)], String::from(",")), "1-2,-2-"),
+            (Mode::Fields(fields(&[1, -1]), String::from(",")), "1,-1"),
+            (Mode::Fields(vec![FieldRange::new(1, 2), FieldRange::new(4, -1)], String::from(",")), "1-2,4-"),
+            (Mode::Characters(spans(&[0..5, 6..7])), "1-5,7"),
+            (Mode::Fields(fields(&[1, -3]), String::from(",")), "1,-3"),
+            (Mode::Characters(vec![FieldRange::new(-5, -3), FieldRange::new(2, -2)]), "-5--3,2--2"),
+        ];
+
+        for (mode, expected) in tests {
+            assert_eq!(expected, mode.spec());
+        }
+
+        let ranges = vec![FieldRange::single(-3), FieldRange::new(-3, -1), FieldRange::new(-5, -3), FieldRange::new(2, -1)];
+        for range in ranges {
+            assert_eq!(Ok(range), range.to_string().parse());
+        }
+    }
+
+    #[test]
+    fn test_cut_reader_with_sep() {
+        let tests = vec![
+            ("a,b\nc,d\n", b'\n', vec!["b", "d"]),
+            ("a,b\0c,d\ne\0", b'\0', vec!["b", "d\ne"]),
+        ];
+
+        for (input, sep, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(",")));
+
+            assert_eq!(expected, cutter.cut_reader_with_sep(input.as_bytes(), sep).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cut_reader_with_sep_invalid_utf8() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(",")));
+
+        let result = cutter.cut_reader_with_sep(&b"a\n\xff\n"[..], b'\n');
+
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let pipeline = Cutter::new(Mode::Fields(fields(&[2, 3]), String::from(",")))
+            .then(Cutter::new(Mode::Fields(fields(&[2]), String::from(","))))
+            .then(Cutter::new(Mode::Characters(spans(&[Range { start: 0, end: 3 }]))));
+
+        let actual = pipeline.cut("a,b,hello\nc,d,elephant\n".as_bytes()).unwrap();
+
+        assert_eq!(vec!["hel", "ele"], actual);
+    }
+
+    #[test]
+    fn test_format() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 2, 3]), String::from(",")))
+            .with_format("2:05".parse().unwrap())
+            .with_format("3:<4".parse().unwrap());
+
+        let actual = cutter.cut("a,42,b\nc,-7,d\ne,123456,f\n".as_bytes()).unwrap();
+
+        assert_eq!(vec!["a,00042,b   ", "c,-0007,d   ", "e,123456,f   "], actual);
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(Ok(FieldFormat { field: 2, align: Align::Right, fill: ' ', width: 8 }), "2:>8".parse());
+        assert_eq!(Ok(FieldFormat { field: 1, align: Align::Right, fill: '0', width: 5 }), "1:05".parse());
+        assert!("0:5".parse::<FieldFormat>().is_err());
+        assert!("1:x".parse::<FieldFormat>().is_err());
+    }
+
+    #[test]
+    fn test_excluded_names() {
+        let cutter = Cutter::new(Mode::Fields(Vec::new(), String::from(",")))
+            .with_excluded_names(vec![String::from("password"), String::from("ssn"), String::from("pin")]);
+        let input = "name,password,email,ssn\nalice,secret,a@example.com,123\n";
+
+        assert_eq!(vec!["name,email", "alice,a@example.com"], cutter.cut(input.as_bytes()).unwrap());
+        assert_eq!(vec!["pin"], cutter.missing_names("name,password,email,ssn"));
+    }
+
+    #[test]
+    fn test_conditional_specs() {
+        let specs = vec!["3:1,2".parse().unwrap(), "5:1,3,5".parse().unwrap()];
+        let input = "a,b,c\na,b,c,d,e\na,b\n";
+
+        struct TestCase {
+            drop_unmatched: bool,
+            expected: Vec<&'static str>,
+            lines_suppressed: usize,
+        }
+        let tests = vec![
+            TestCase {
+                drop_unmatched: false,
+                expected: vec!["a,b", "a,c,e", "a,b"],
+                lines_suppressed: 0,
+            },
+            TestCase {
+                drop_unmatched: true,
+                expected: vec!["a,b", "a,c,e"],
+                lines_suppressed: 1,
+            },
+        ];
+
+        for test in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(",")))
+                .with_conditional_specs(specs.clone(), test.drop_unmatched);
+
+            let (actual, stats) = cutter.cut_with_stats(input.as_bytes()).unwrap();
+
+            assert_eq!(test.expected, actual);
+            assert_eq!(test.lines_suppressed, stats.lines_suppressed);
+        }
+    }
+
+    #[test]
+    fn test_cut_bytes() {
+        let cutter = Cutter::new(Mode::Bytes(spans(&[1..3, 4..5])));
+        let input = [b'a', 0xFF, 0xFE, b'b', 0x00, b'\n', 0xC3, 0x28, b'c', b'd', b'e', b'\n'];
+        let mut output = Vec::new();
+
+        let written = cutter.cut_bytes(&input[..], &mut output, b'\n').unwrap();
+
+        assert_eq!(vec![0xFF, 0xFE, 0x00, b'\n', 0x28, b'c', b'e', b'\n'], output);
+        assert_eq!(6, written);
+    }
+
+    #[test]
+    fn test_field_count_histogram() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(",")));
+        let input = "a,b,c\nd,e\nf,g,h\n\ni,j,k,l\n";
+
+        let histogram = cutter.field_count_histogram(input.as_bytes()).unwrap();
+
+        assert_eq!(HashMap::from([(1, 1), (2, 1), (3, 2), (4, 1)]), histogram);
+    }
+
+    #[test]
+    fn test_csv() {
+        struct TestCase {
+            csv: bool,
+            line: &'static str,
+            expected: Vec<String>,
+        }
+        let tests = vec![
+            TestCase {
+                csv: true,
+                line: r#"1,"Doe, Jane",x"#,
+                expected: vec![String::from("Doe, Jane,x")],
+            },
+            TestCase {
+                csv: true,
+                line: r#"1,"say ""hi""",x"#,
+                expected: vec![String::from(r#"say "hi",x"#)],
+            },
+            TestCase {
+                csv: true,
+                line: r#"1,,"""#,
+                expected: vec![String::from(",")],
+            },
+            TestCase {
+                csv: false,
+                line: r#"1,"Doe, Jane",x"#,
+                expected: vec![String::from(r#""Doe, Jane""#)],
+            },
+        ];
+
+        for test in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[2, 3]), String::from(","))).with_csv(test.csv);
+            assert_eq!(test.expected, cutter.cut(test.line.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fields_from_both_ends() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, -1]), String::from(",")));
+        let input = "a,b,c,d\na,b\na\n\n";
+
+        let expected = vec!["a,d", "a,b", "a", ""];
+        assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+
+        let cutter = Cutter::new(Mode::Fields(fields(&[2, -2, -5]), String::from(",")));
+        let expected = vec!["b,c", "b,a", "a", ""];
+        assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_strip_quotes() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 2, 3, 4]), String::from(","))).with_strip_quotes(true);
+        let input = r#""value","unbalanced,"a"b",""""#;
+
+        let expected = vec![r#"value,"unbalanced,a"b,""#];
+        assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_hash() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_hash(HashAlgorithm::Fnv, 8);
+        let input = "1,a\n2,b\n3,a\n";
+
+        let expected = vec!["af63dc4c a", "af63df4c b", "af63dc4c a"];
+        assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+
+        let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_hash(HashAlgorithm::Sip, 4);
+        let output = cutter.cut(input.as_bytes()).unwrap();
+        assert_eq!(output[0], output[2]);
+        assert_ne!(output[0], output[1]);
+        assert_eq!(6, output[0].len());
+    }
+
+    #[test]
+    fn test_numeric_filter() {
+        let input = "a,1\nb,x\nc,-2.5e3\nd\ne,\n";
+        let tests = vec![
+            (true, vec!["a", "c"]),
+            (false, vec!["b", "d", "e"]),
+        ];
+
+        for (numeric, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(","))).with_numeric_filter(2, numeric);
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cut_map() {
+        let cutter = Cutter::new(Mode::Fields(Vec::new(), String::from("\t")));
+        let input = "de\tGermany\nfr\tFrance\nnl\nde\tDeutschland\n";
+
+        let map = cutter.cut_map(input.as_bytes(), 1, 2, DuplicateKeys::LastWins).unwrap();
+        let expected = HashMap::from([
+            (String::from("de"), String::from("Deutschland")),
+            (String::from("fr"), String::from("France")),
+        ]);
+        assert_eq!(expected, map);
+
+        let result = cutter.cut_map(input.as_bytes(), 1, 2, DuplicateKeys::Error);
+        assert_eq!("duplicate key de on line 4", result.unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_cut_table() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 2, 3]), String::from(",")));
+        let input = "name,city,age\nann,東京,30\nbartholomew,Rome\n";
+
+        let expected = vec![
+            "name         city  age",
+            "-----------  ----  ---",
+            "ann          東京  30",
+            "bartholomew  Rome",
+        ];
+        assert_eq!(expected, cutter.cut_table(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_line_length() {
+        struct TestCase {
+            unit: LengthUnit,
+            expected: Vec<&'static str>,
+        }
+        let tests = vec![
+            TestCase {
+                unit: LengthUnit::Characters,
+                expected: vec!["abc", "äöü", "abcd"],
+            },
+            TestCase {
+                unit: LengthUnit::Bytes,
+                expected: vec!["abc", "abcd"],
+            },
+        ];
+        let input = "ab\nabc\näöü\nabcd\nabcde\n";
+
+        for test in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(","))).with_line_length(3..=4, test.unit);
+            assert_eq!(test.expected, cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_annotate() {
+        let tests = vec![
+            (Mode::Fields(fields(&[2]), String::from(",")), "a,[b],c"),
+            (Mode::Fields(fields(&[1, -1]), String::from(",")), "[a],b,[c]"),
+            (Mode::Characters(spans(&[Range { start: 1, end: 3 }])), "a[,b],c"),
+            (Mode::Bytes(spans(&[0..1, 4..5])), "[a],b,[c]"),
+        ];
+
+        for (mode, expected) in tests {
+            let cutter = Cutter::new(mode).with_annotate(true);
+            assert_eq!(vec![expected], cutter.cut("a,b,c".as_bytes()).unwrap());
+        }
+
+        let tests = vec![
+            (Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_csv(true), "\"a,b\",c", "\"a,b\",[c]"),
+            (Cutter::new(Mode::Fields(fields(&[1]), String::from(","))).with_csv(true), "\"a,\"\"b\",c", "[\"a,\"\"b\"],c"),
+            (Cutter::new(Mode::Fields(fields(&[2]), String::new())).with_whitespace(true), "a   b c", "a   [b] c"),
+            (Cutter::new(Mode::Fields(fields(&[1, 3]), String::new())).with_whitespace(true), "  a b  c ", "  [a] b  [c] "),
+            (Cutter::new(Mode::Fields(fields(&[1, 2]), String::new())), "abc", "[ab]c"),
+            (Cutter::new(Mode::Fields(fields(&[3]), String::from(","))).with_max_fields(2), "a,b,c", "a,b,c"),
+        ];
+
+        for (cutter, line, expected) in tests {
+            let cutter = cutter.with_annotate(true);
+            assert_eq!(vec![expected], cutter.cut(line.as_bytes()).unwrap(), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_split_csv() {
+        let tests = vec![
+            ("a,\"b,c\",d", usize::MAX, vec!["a", "b,c", "d"]),
+            ("\"a\"\"b\",\"\"", usize::MAX, vec!["a\"b", ""]),
+            ("a,\"b,c\",d", 2, vec!["a", "b,c"]),
+            ("x\"y,z", usize::MAX, vec!["x\"y", "z"]),
+        ];
+
+        for (line, limit, expected) in tests {
+            assert_eq!(expected, split_csv(line, ",", limit), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_sniff_delimiter() {
+        let tests = vec![
+            ("a,b,c", Some(',')),
+            ("a\tb,c\td", Some('\t')),
+            ("a;b;c,d", Some(';')),
+            ("a|b:c", Some('|')),
+            ("abc", None),
+        ];
+
+        for (line, expected) in tests {
+            assert_eq!(expected, sniff_delimiter(line));
+        }
+    }
+
+    #[test]
+    fn test_selected_indices() {
+        struct TestCase {
+            cutter: Cutter,
+            line: &'static str,
+            expected: Vec<usize>,
+        }
+        let tests = vec![
+            TestCase {
+                cutter: Cutter::new(Mode::Fields(fields(&[1, 3, -1, 7]), String::from(","))),
+                line: "a,b,c,d",
+                expected: vec![1, 3, 4],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Fields(fields(&[3, 4]), String::from(","))).with_max_fields(3),
+                line: "a,b,c,d",
+                expected: vec![3],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Characters(spans(&[Range { start: 1, end: 10 }]))),
+                line: "äbc",
+                expected: vec![2, 3],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Bytes(spans(&[0..1, 3..10]))),
+                line: "äbc",
+                expected: vec![1, 4],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Characters(spans(&[Range { start: 5, end: 10 }]))),
+                line: "abc",
+                expected: vec![],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Fields(fields(&[1, -1]), String::from(","))).with_complement(true),
+                line: "a,b,c,d",
+                expected: vec![2, 3],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Bytes(spans(&[Range { start: 1, end: 3 }]))).with_complement(true),
+                line: "äbc",
+                expected: vec![1, 4],
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(test.expected, test.cutter.selected_indices(test.line));
+        }
+    }
+
+    #[test]
+    fn test_clamped_ranges() {
+        let cutter = Cutter::new(Mode::Characters(spans(&[0..2, 3..10])));
+        assert_eq!(vec!["ab", "abde", "ab"], cutter.cut("ab\nabcde\nabc\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_lines() {
+        let cutter = Cutter::new(Mode::Characters(spans(&[Range { start: 0, end: 2 }])))
+            .with_short_line_policy(ShortLinePolicy::Error, ' ');
+        let mut output = Vec::new();
+
+        for line in cutter.cut_lines("abc\nde\nf\ngh\n".as_bytes()) {
+            match line {
+                Ok(line) => output.push(line),
+                Err(err) => {
+                    assert_eq!("line 3 is shorter than the selection", err.to_string());
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(vec!["ab", "de"], output);
+    }
+
+    #[test]
+    fn test_parse_field_range() {
+        let tests = vec![
+            ("3", Ok(FieldRange::single(3))),
+            ("2-4", Ok(FieldRange::new(2, 4))),
+            ("3-", Ok(FieldRange::new(3, -1))),
+            ("-2", Ok(FieldRange::single(-2))),
+            ("-2-", Ok(FieldRange::new(-2, -1))),
+            ("-3--2", Ok(FieldRange::new(-3, -2))),
+            ("2--2", Ok(FieldRange::new(2, -2))),
+            ("-2--3", Err(String::from("invalid decreasing range -2--3"))),
+            ("-", Err(String::from("invalid field range -, expected N, N-M, N-, -N or -N-"))),
+            ("0", Err(String::from("invalid field range 0, fields are numbered from 1"))),
+            ("0-2", Err(String::from("invalid field range 0-2, fields are numbered from 1"))),
+            ("5-3", Err(String::from("invalid decreasing range 5-3"))),
+            ("3-3", Ok(FieldRange::single(3))),
+        ];
+
+        for (s, expected) in tests {
+            assert_eq!(expected, s.parse::<FieldRange>());
+        }
+    }
+
+    #[test]
+    fn test_field_ranges() {
+        let input = "a,b,c,d,e,f\na,b\n\n";
+        let tests = vec![
+            ("3-", vec!["c,d,e,f", "", ""]),
+            ("1-2", vec!["a,b", "a,b", ""]),
+            ("-2", vec!["e", "a", ""]),
+            ("-1", vec!["f", "b", ""]),
+            ("-3", vec!["d", "", ""]),
+            ("-2-", vec!["e,f", "a,b", ""]),
+            ("2-4", vec!["b,c,d", "b", ""]),
+        ];
+
+        for (range, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(vec![range.parse().unwrap()], String::from(",")));
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap(), "{range}");
+        }
+    }
+
+    #[test]
+    fn test_complement() {
+        struct TestCase {
+            mode: Mode,
+            input: &'static str,
+            expected: Vec<&'static str>,
+        }
+        let tests = vec![
+            TestCase {
+                mode: Mode::Fields(fields(&[2]), String::from("\t")),
+                input: "a\tb\tc\n",
+                expected: vec!["a\tc"],
+            },
+            TestCase {
+                mode: Mode::Fields(fields(&[2]), String::from(",")),
+                input: "a,b,c\na\n",
+                expected: vec!["a,c", "a"],
+            },
+            TestCase {
+                mode: Mode::Fields(Vec::new(), String::from(",")),
+                input: "a,b,c\n",
+                expected: vec!["a,b,c"],
+            },
+            TestCase {
+                mode: Mode::Characters(spans(&[1..3, 2..4])),
+                input: "abcdef\nab\n",
+                expected: vec!["aef", "a"],
+            },
+            TestCase {
+                mode: Mode::Characters(Vec::new()),
+                input: "äbc\n",
+                expected: vec!["äbc"],
+            },
+            TestCase {
+                mode: Mode::Bytes(spans(&[0..1, 4..10])),
+                input: "abcdef\n",
+                expected: vec!["bcd"],
+            },
+        ];
+
+        for test in tests {
+            let cutter = Cutter::new(test.mode).with_complement(true);
+            assert_eq!(test.expected, cutter.cut(test.input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_only_delimited() {
+        let input = "a,b,c\nno delimiter\n\n1,2\n";
+        let tests = vec![
+            (false, vec!["b", "no delimiter", "", "2"]),
+            (true, vec!["b", "2"]),
+        ];
+
+        for (only_delimited, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_only_delimited(only_delimited);
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cut_read_error() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(",")));
+        let input: &[u8] = b"a,b\n\xff,c\nd,e\n";
+
+        let err = cutter.cut(input).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        let pipeline = cutter.clone().then(cutter);
+        assert!(pipeline.cut(input).is_err());
+    }
+
+    /// Generates `lines` copies of `line` without holding them in memory, counting the bytes read.
+    struct Repeat {
+        line: &'static [u8],
+        lines: usize,
+        offset: usize,
+        read: Rc<Cell<usize>>,
+    }
+
+    impl io::Read for Repeat {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.lines == 0 {
+                return Ok(0);
+            }
+            let rest = &self.line[self.offset..];
+            let len = rest.len().min(buf.len());
+            buf[..len].copy_from_slice(&rest[..len]);
+            self.offset += len;
+            if self.offset == self.line.len() {
+                self.offset = 0;
+                self.lines -= 1;
+            }
+            self.read.set(self.read.get() + len);
+            Ok(len)
+        }
+    }
+
+    /// Counts the bytes written and remembers how much input had been read at the first write.
+    struct Sink {
+        written: usize,
+        read_at_first_write: Option<usize>,
+        read: Rc<Cell<usize>>,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.read_at_first_write.get_or_insert(self.read.get());
+            self.written += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cut_to_writer() {
+        let lines = 250_000;
+        let read = Rc::new(Cell::new(0));
+        let reader = BufReader::new(Repeat { line: b"a,bb,c\n", lines, offset: 0, read: read.clone() });
+        let mut sink = Sink { written: 0, read_at_first_write: None, read: read.clone() };
+        let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(",")));
+
+        cutter.cut_to_writer(reader, &mut sink).unwrap();
+
+        assert_eq!(7 * lines, read.get());
+        assert_eq!(3 * lines, sink.written);
+        assert!(sink.read_at_first_write.unwrap() < 64 * 1024);
+    }
+
+    #[test]
+    fn test_cut_lines() {
+        let lines = 250_000;
+        let read = Rc::new(Cell::new(0));
+        let reader = BufReader::new(Repeat { line: b"a,bb,c\n", lines, offset: 0, read: read.clone() });
+        let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(",")));
+
+        let output = cutter.cut_lines(reader)
+            .take(2)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(vec!["bb", "bb"], output);
+        assert!(read.get() < 7 * lines);
+
+        #[allow(deprecated)]
+        let output = cutter.lines("a,bb\n".as_bytes()).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(vec!["bb"], output);
+    }
+
+    #[test]
+    fn test_terminator() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_terminator(b'\0');
+        let input = "a,b\0c,d\ne\0f,g\0";
+
+        assert_eq!(vec!["b", "d\ne", "g"], cutter.cut(input.as_bytes()).unwrap());
+
+        let mut out = Vec::new();
+        cutter.cut_to_writer(input.as_bytes(), &mut out).unwrap();
+        assert_eq!(b"b\0d\ne\0g\0".to_vec(), out);
+
+        let lines = cutter.cut_lines(input.as_bytes()).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(vec!["b", "d\ne", "g"], lines);
+        assert_eq!(vec!["b,d\ne,g"], cutter.cut_transposed(input.as_bytes(), "").unwrap());
+        assert_eq!(vec!["b", "---", "d\ne", "g"], cutter.cut_table(input.as_bytes()).unwrap());
+        assert_eq!(vec!["a b", "c d\ne", "f g"], cutter.cut_grouped(input.as_bytes(), 1).unwrap());
+        assert_eq!(HashMap::from([(2, 3)]), cutter.field_count_histogram(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_no_split_chars() {
+        let tests = vec![
+            (vec![Range { start: 0, end: 2 }], "héllo", "hé"),
+            (vec![Range { start: 0, end: 3 }], "héllo", "hé"),
+            (vec![Range { start: 2, end: 4 }], "héllo", "l"),
+            (vec![1..2, 4..5], "héllo", "él"),
+            (vec![Range { start: 0, end: 2 }], "€uro", "€"),
+            (vec![Range { start: 1, end: 3 }], "€uro", ""),
+        ];
+
+        for (ranges, input, expected) in tests {
+            let cutter = Cutter::new(Mode::Bytes(spans(&ranges))).with_no_split_chars(true);
+            assert_eq!(vec![expected], cutter.cut(input.as_bytes()).unwrap());
+            assert_eq!(expected.as_bytes(), cutter.filter_bytes(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_string_delimiter() {
+        let tests = vec![
+            ("::", "a::b::c\nd:e::f\n", vec!["a::c", "d:e"]),
+            (", ", "a, b, c\nd,e, f\n", vec!["a, c", "d,e"]),
+        ];
+
+        for (delimiter, input, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1, 3]), String::from(delimiter)));
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+
+            let cutter = cutter.with_csv(true);
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1, 3]), String::from("\t"))).with_whitespace(true);
+        let input = "  a   b  c \nd\te f\n\nsingle\n   \n";
+
+        let expected = vec!["a c", "d f", "", "single", ""];
+        assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+        assert_eq!(vec![1, 3], cutter.selected_indices("  a   b  c "));
+
+        let cutter = cutter.with_only_delimited(true);
+        assert_eq!(vec!["a c", "d f", ""], cutter.cut(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_ranges_from_end() {
+        struct TestCase {
+            cutter: Cutter,
+            expected: Vec<&'static str>,
+        }
+        let tests = vec![
+            TestCase {
+                cutter: Cutter::new(Mode::Characters(vec![FieldRange::new(-3, -1)])),
+                expected: vec!["éfg", "ab", ""],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Characters(vec![FieldRange::single(1), FieldRange::single(-1)])),
+                expected: vec!["ag", "ab", ""],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Characters(vec![FieldRange::new(-3, -2)])).with_complement(true),
+                expected: vec!["abcdg", "b", ""],
+            },
+            TestCase {
+                cutter: Cutter::new(Mode::Bytes(vec![FieldRange::new(-3, -1)])),
+                expected: vec!["\u{FFFD}fg", "ab", ""],
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(test.expected, test.cutter.cut("abcdéfg\nab\n\n".as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_strip_cr() {
+        let input = "a\tb\r\nc\td\n";
+        let cutter = Cutter::new(Mode::Fields(fields(&[2]), String::from("\t")));
+
+        assert_eq!(vec!["b", "d"], cutter.cut(input.as_bytes()).unwrap());
+        assert_eq!(vec!["b", "d"], cutter.cut_reader_with_sep(input.as_bytes(), b'\n').unwrap());
+        let mut out = Vec::new();
+        cutter.cut_bytes(input.as_bytes(), &mut out, b'\n').unwrap();
+        assert_eq!(b"b\nd\n".to_vec(), out);
+
+        assert_eq!(vec!["b", "d"], cutter.cut_lines(input.as_bytes()).collect::<io::Result<Vec<_>>>().unwrap());
+        assert_eq!(vec!["b\td"], cutter.cut_transposed(input.as_bytes(), "").unwrap());
+
+        let cutter = cutter.with_strip_cr(false);
+        assert_eq!(vec!["b\r", "d"], cutter.cut(input.as_bytes()).unwrap());
+        assert_eq!(vec!["b\r", "d"], cutter.cut_reader_with_sep(input.as_bytes(), b'\n').unwrap());
+        assert_eq!(vec!["b\r", "d"], cutter.cut_lines(input.as_bytes()).collect::<io::Result<Vec<_>>>().unwrap());
+        assert_eq!(vec!["b\r\td"], cutter.cut_transposed(input.as_bytes(), "").unwrap());
+
+        let cutter = Cutter::new(Mode::Bytes(spans(&[Range { start: 2, end: 4 }])));
+        let mut out = Vec::new();
+        cutter.cut_bytes(input.as_bytes(), &mut out, b'\n').unwrap();
+        assert_eq!(b"b\r\nd\n".to_vec(), out);
+        assert_eq!(vec!["b\r", "d"], cutter.cut(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_builder() {
+        let cutter = CutterBuilder::new()
+            .fields(vec![FieldRange::single(2)])
+            .delimiter(String::from(","))
+            .output_delimiter(String::from(";"))
+            .complement(true)
+            .only_delimited(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(vec!["a;c"], cutter.cut("a,b,c\nd\n".as_bytes()).unwrap());
+
+        let cutter = CutterBuilder::new()
+            .characters(spans(&[Range { start: 0, end: 2 }]))
+            .build()
+            .unwrap();
+
+        assert_eq!(vec!["ab"], cutter.cut("abc\n".as_bytes()).unwrap());
+
+        let cutter = CutterBuilder::new()
+            .graphemes(spans(&[Range { start: 1, end: 2 }]))
+            .build()
+            .unwrap();
+
+        assert_eq!(vec!["e\u{301}"], cutter.cut("ae\u{301}c\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_builder_invalid() {
+        let tests = vec![
+            (
+                CutterBuilder::new().characters(spans(&[Range { start: 0, end: 1 }])).delimiter(String::from(",")),
+                "an input delimiter may be specified only when operating on fields",
+            ),
+            (
+                CutterBuilder::new().bytes(spans(&[Range { start: 0, end: 1 }])).only_delimited(true),
+                "suppressing non-delimited lines makes sense only when operating on fields",
+            ),
+            (
+                CutterBuilder::new().fields(vec![FieldRange::single(1)]).bytes(spans(&[Range { start: 0, end: 1 }])),
+                "only one of fields, characters, graphemes or bytes may be selected",
+            ),
+            (
+                CutterBuilder::new().graphemes(spans(&[Range { start: 0, end: 1 }])).delimiter(String::from(",")),
+                "an input delimiter may be specified only when operating on fields",
+            ),
+            (
+                CutterBuilder::new().complement(true),
+                "a list of fields, characters, graphemes or bytes is required",
+            ),
+        ];
+
+        for (builder, expected) in tests {
+            assert_eq!(Some(String::from(expected)), builder.build().err());
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let tests = vec![
+            (Mode::Fields(vec![FieldRange::single(1), FieldRange::single(3)], String::from("\t")), "a\tb\tc\n", vec![r#"["a","c"]"#]),
+            (Mode::Fields(vec![FieldRange::new(1, 2)], String::from(",")), "say \"hi\",C:\\dir\n", vec![r#"["say \"hi\"","C:\\dir"]"#]),
+            (Mode::Fields(vec![FieldRange::single(2)], String::from(",")), "a,\u{1}\nb\n", vec![r#"["\u0001"]"#, r#"["b"]"#]),
+            (Mode::Characters(spans(&[Range { start: 0, end: 2 }])), "abc\n", vec![r#"["ab"]"#]),
+        ];
+
+        for (mode, input, expected) in tests {
+            let cutter = Cutter::new(mode).with_json(true);
+
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_field_names() {
+        let input = "id,name,email\n1,ann,ann@example.com\n2,bob,bob@example.com\n";
+        let tests = vec![
+            (vec!["name", "email"], Ok(vec!["name,email", "ann,ann@example.com", "bob,bob@example.com"])),
+            (vec!["email", "id"], Ok(vec!["email,id", "ann@example.com,1", "bob@example.com,2"])),
+            (vec!["name", "phone"], Err("unknown column phone, available columns: id, name, email")),
+        ];
+
+        for (names, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(Vec::new(), String::from(",")))
+                .with_field_names(names.into_iter().map(String::from).collect());
+
+            let result = cutter.cut(input.as_bytes()).map_err(|err| err.to_string());
+            let lines = cutter.cut_lines(input.as_bytes()).collect::<Result<Vec<_>, _>>().map_err(|err| err.to_string());
+
+            assert_eq!(expected.map(|lines| lines.into_iter().map(String::from).collect::<Vec<_>>()).map_err(String::from), result);
+            assert_eq!(result, lines);
+        }
+    }
+
+    #[test]
+    fn test_field_names_unknown_whole_input() {
+        let input = "id,name
+1,ann
+";
+        let cutter = Cutter::new(Mode::Fields(Vec::new(), String::from(",")))
+            .with_field_names(vec![String::from("phone")]);
+        let expected = Some(String::from("unknown column phone, available columns: id, name"));
+
+        assert_eq!(expected, cutter.cut_transposed(input.as_bytes(), "").err().map(|err| err.to_string()));
+        assert_eq!(expected, cutter.cut_table(input.as_bytes()).err().map(|err| err.to_string()));
+        assert_eq!(expected, cutter.cut_grouped(input.as_bytes(), 1).err().map(|err| err.to_string()));
+        assert_eq!(expected, cutter.cut_reader_with_sep(input.as_bytes(), b'\n').err().map(|err| err.to_string()));
+        assert_eq!(expected, cutter.field_count_histogram(input.as_bytes()).err().map(|err| err.to_string()));
+        assert_eq!(expected, cutter.cut_map(input.as_bytes(), 1, 2, DuplicateKeys::Error).err().map(|err| err.to_string()));
+    }
+
+    #[test]
+    fn test_select_slices() {
+        let tests = vec![
+            (Cutter::new(Mode::Fields(fields(&[3, 1]), String::from(","))), "a,bb,ccc", Some(vec!["ccc", "a"])),
+            (Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_complement(true), "a,bb,ccc", Some(vec!["a", "ccc"])),
+            (Cutter::new(Mode::Fields(fields(&[2]), String::from(","))), "abc", Some(vec!["abc"])),
+            (Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_only_delimited(true), "abc", None),
+            (Cutter::new(Mode::Fields(fields(&[2]), String::new())).with_whitespace(true), " a  b ", Some(vec!["b"])),
+            (Cutter::new(Mode::Characters(spans(&[Range { start: 1, end: 3 }, Range { start: 4, end: 9 }]))), "aéçdë", Some(vec!["éç", "ë"])),
+            (Cutter::new(Mode::Bytes(spans(&[Range { start: 0, end: 1 }]))), "abc", None),
+            (Cutter::new(Mode::Fields(fields(&[3]), String::from(","))).with_max_fields(2), "a,b,c", Some(vec![])),
+            (Cutter::new(Mode::Fields(fields(&[2]), String::from(","))).with_max_fields(2), "a,b,c", Some(vec!["b"])),
+        ];
+
+        for (cutter, line, expected) in tests {
+            let line = line.to_string();
+            let slices = cutter.select_slices(&line);
+
+            assert_eq!(expected, slices);
+            let buffer = line.as_bytes().as_ptr_range();
+            for slice in slices.unwrap_or_default() {
+                assert!(buffer.contains(&slice.as_ptr()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_reuses_buffer() {
+        let cutter = Cutter::new(Mode::Characters(spans(&[Range { start: 1, end: 3 }, Range { start: 5, end: 7 }])));
+        let lines = ["abcdefgh", "xy", "", "äöüßéèê", "z"];
+        let mut chars = Vec::new();
+        let mut output = String::new();
+
+        for line in lines {
+            let kept = cutter.filter_into(line, &mut chars, &mut None, &mut output);
+            assert_eq!(cutter.filter(line), kept.then(|| output.clone()));
+        }
+        assert!(chars.capacity() >= 7);
+        assert!(output.capacity() >= 4);
+        assert_eq!(vec!["bcfg", "y", "", "öüèê", ""], cutter.cut(lines.join("\n").as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        let tests = vec![
+            (8, Range { start: 8, end: 11 }, "ab\tcde\tx", "cde"),
+            (8, Range { start: 14, end: 17 }, "ab\tcde\tx", "  x"),
+            (4, Range { start: 4, end: 7 }, "abcd\tef", "   "),
+            (4, Range { start: 8, end: 10 }, "abcd\tef", "ef"),
+            (4, Range { start: 0, end: 3 }, "abc", "abc"),
+        ];
+
+        for (tab_width, range, line, expected) in tests {
+            let cutter = Cutter::new(Mode::Characters(spans(&[range]))).with_expand_tabs(tab_width);
+
+            assert_eq!(vec![expected], cutter.cut(line.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_expand_tabs_short_line() {
+        let cutter = Cutter::new(Mode::Characters(spans(&[Range { start: 4, end: 6 }])))
+            .with_expand_tabs(8)
+            .with_short_line_policy(ShortLinePolicy::Error, ' ');
+
+        assert_eq!(vec!["  "], cutter.cut("a\tb\n".as_bytes()).unwrap());
+        assert_eq!("line 1 is shorter than the selection", cutter.cut("abc\n".as_bytes()).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let accented = "e\u{301}";
+        let tests = vec![
+            (vec![Range { start: 1, end: 2 }], format!("a{family}b"), vec![family.to_string()]),
+            (vec![Range { start: 0, end: 2 }], format!("{accented}x{accented}"), vec![format!("{accented}x")]),
+            (vec![Range { start: 2, end: 3 }], format!("{accented}x{accented}"), vec![accented.to_string()]),
+            (vec![Range { start: 1, end: 2 }], String::from("a\u{1F1E9}\u{1F1EA}\u{1F1EB}\u{1F1F7}"), vec![String::from("\u{1F1E9}\u{1F1EA}")]),
+            (vec![Range { start: 0, end: 1 }], String::from("\u{1F44D}\u{1F3FD}!"), vec![String::from("\u{1F44D}\u{1F3FD}")]),
+        ];
+
+        for (ranges, line, expected) in tests {
+            let cutter = Cutter::new(Mode::Graphemes(spans(&ranges)));
+
+            assert_eq!(expected, cutter.cut(line.as_bytes()).unwrap());
+            assert_eq!(Some(expected.iter().map(String::as_str).collect()), cutter.select_slices(&line));
+        }
+
+        let cutter = Cutter::new(Mode::Characters(spans(&[Range { start: 0, end: 1 }])));
+        assert_eq!(vec!["e"], cutter.cut(accented.as_bytes()).unwrap());
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn test_graphemes_segmentation() {
+        let tests = vec![
+            ("\u{915}\u{93F}x", vec!["\u{915}\u{93F}", "x"]),
+            ("\u{E01}\u{E33}x", vec!["\u{E01}\u{E33}", "x"]),
+            ("\u{1100}\u{1161}\u{11A8}x", vec!["\u{1100}\u{1161}\u{11A8}", "x"]),
+            ("\u{1F469}\u{200D}\u{1F4BB}x", vec!["\u{1F469}\u{200D}\u{1F4BB}", "x"]),
+            ("a\u{200D}b", vec!["a\u{200D}", "b"]),
+        ];
+
+        for (line, expected) in tests {
+            assert_eq!(expected, graphemes(line), "{line}");
+
+            let cutter = Cutter::new(Mode::Graphemes(spans(&[Range { start: 0, end: 1 }])));
+            assert_eq!(vec![expected[0]], cutter.cut(line.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_with_lines() {
+        let input = "a,1\nb,2\nc,3\nd,4\n";
+        let tests = vec![
+            (vec![Range { start: 0, end: 1 }, Range { start: 2, end: 3 }], vec!["a", "c"]),
+            (vec![Range { start: 1, end: 3 }], vec!["b", "c"]),
+            (vec![Range { start: 3, end: 9 }], vec!["d"]),
+            (Vec::new(), vec!["a", "b", "c", "d"]),
+        ];
+
+        for (lines, expected) in tests {
+            let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(","))).with_lines(lines);
+
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+            assert_eq!(expected, cutter.cut_lines(input.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap());
+            assert_eq!(expected, cutter.cut_reader_with_sep(input.as_bytes(), b'\n').unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cut_bytes_options() {
+        let tests = vec![
+            (Cutter::new(Mode::Bytes(spans(&[0..1, 2..3]))).with_count_only(true), "abc\nd\n", "2\n1\n"),
+            (Cutter::new(Mode::Bytes(spans(&[0..1, 1..3]))).with_lengths(LengthUnit::Bytes).with_output_delimiter(String::from(",")), "abc\nd\n", "1,2\n1\n"),
+            (Cutter::new(Mode::Bytes(spans(&[0..1, 2..3]))).with_line_length(2..=3, LengthUnit::Bytes), "abc\nd\nef\n", "ac\ne\n"),
+            (Cutter::new(Mode::Bytes(spans(&[0..1, 2..3]))).with_numeric_filter(1, true), "1.5\nabc\n", "15\n"),
+        ];
+
+        for (cutter, input, expected) in tests {
+            let mut output = Vec::new();
+
+            cutter.cut_bytes(input.as_bytes(), &mut output, b'\n').unwrap();
+
+            assert_eq!(expected, String::from_utf8(output).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_whole_input_read_errors() {
+        let cutter = Cutter::new(Mode::Fields(fields(&[1]), String::from(",")));
+        let input: &[u8] = b"a,b\n\xFF\n";
+
+        assert!(cutter.cut_transposed(input, "").is_err());
+        assert!(cutter.cut_table(input).is_err());
+        assert!(cutter.cut_grouped(input, 1).is_err());
+        assert!(cutter.cut_map(input, 1, 2, DuplicateKeys::LastWins).is_err());
+    }
+}
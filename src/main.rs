@@ -1,65 +1,716 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::env;
 use std::fmt::Display;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, stdin};
+use std::io::{BufRead, IsTerminal, Read, stdin, Write};
+use std::ops::Range;
+use std::process::ExitCode;
 use std::str::FromStr;
+use std::thread;
 
-use cccut::{Cutter, Mode};
-use cccut::flags::{FlagSet, Value};
+use cccut::{ConditionalSpec, Cutter, CutterBuilder, FieldRange, HashAlgorithm, LengthUnit, Mode, ShortLinePolicy, sniff_delimiter};
+use cccut::decompress;
+use cccut::decompress::Compression;
+use cccut::encoding::{Encoding, Unmappable};
+use cccut::flags::{FlagError, FlagSet, Value};
 
-fn main() -> Result<(), String> {
+const NO_MATCH_EXIT_CODE: u8 = 1;
+
+fn main() -> Result<ExitCode, String> {
     let args = env::args().skip(1);
-    let (cutter, remaining) = create_cutter(args)?;
-    run(cutter, remaining)
+    let (cutter, mut options, remaining) = match create_cutter(args)? {
+        Parsed::Run(run) => *run,
+        Parsed::Help(usage) => {
+            print!("{usage}");
+            return Ok(ExitCode::SUCCESS);
+        }
+        Parsed::Version => {
+            println!("cccut {}", env!("CARGO_PKG_VERSION"));
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    options.stdin_is_terminal = stdin().is_terminal();
+    echo_spec(&cutter, &options, &mut io::stderr())?;
+    if options.interactive {
+        let tty = File::open("/dev/tty").map_err(|err| format!("Can not open the terminal for --interactive: {err}"))?;
+        options.prompt_input = Some(Box::new(io::BufReader::new(tty)));
+    }
+    let fail_on_empty = options.fail_on_empty;
+    let matched = run_buffered(cutter, options, remaining, io::stdout().lock())?;
+    Ok(exit_code(matched, fail_on_empty))
+}
+
+/// Writes the normalized selection to `err` with `--echo-spec`.
+fn echo_spec(cutter: &Cutter, options: &Options, err: &mut impl Write) -> Result<(), String> {
+    if !options.echo_spec {
+        return Ok(());
+    }
+    writeln!(err, "{}", cutter.mode().spec()).map_err(|err| err.to_string())
+}
+
+fn exit_code(matched: bool, fail_on_empty: bool) -> ExitCode {
+    match !matched && fail_on_empty {
+        true => ExitCode::from(NO_MATCH_EXIT_CODE),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    file_headers: bool,
+    force_file_headers: bool,
+    transpose: bool,
+    table: bool,
+    fill: String,
+    group_by: usize,
+    force_stdin: bool,
+    stdin_is_terminal: bool,
+    output_encoding: Encoding,
+    unmappable: Unmappable,
+    fail_on_empty: bool,
+    warn_missing_names: bool,
+    echo_spec: bool,
+    histogram: bool,
+    parallel_files: bool,
+    delimiter_auto_per_file: bool,
+    decompress: Compression,
+    interactive: bool,
+    zero_terminated: bool,
+    /// Where `--interactive` reads the spec from, the controlling terminal unless set by tests.
+    prompt_input: Option<Box<dyn BufRead + Sync>>,
+}
+
+impl Options {
+    fn terminator(&self) -> u8 {
+        match self.zero_terminated {
+            true => b'\0',
+            false => b'\n',
+        }
+    }
+}
+
+/// What the arguments ask for: cutting the remaining files, or printing the usage or version.
+enum Parsed {
+    Run(Box<(Cutter, Options, Vec<String>)>),
+    Help(String),
+    Version,
 }
 
-fn create_cutter(args: impl IntoIterator<Item=String>) -> Result<(Cutter, Vec<String>), String>
+fn create_cutter(args: impl IntoIterator<Item=String>) -> Result<Parsed, String>
 {
     let mut flag_set = FlagSet::default();
+    let mut options = Options::default();
+
+    let mut fields = ArgList::<String>::default();
+    flag_set.bind_mut_ref("fields", true, &mut fields, "select only these fields, e.g. 1,3-5,7-, -1 for the last one or -2- for the last two");
+
+    let mut characters = ArgList::<String>::default();
+    flag_set.bind_mut_ref("characters", true, &mut characters, "select only these characters, e.g. 1-3,5 or -2- for the last two");
+
+    let mut bytes = ArgList::<String>::default();
+    flag_set.bind_mut_ref("bytes", true, &mut bytes, "select only these bytes, e.g. 1-3,5 or -2- for the last two");
+
+    let mut graphemes = ArgList::<String>::default();
+    flag_set.bind_mut_ref("graphemes", true, &mut graphemes, "select only these grapheme clusters, keeping accents and emoji sequences whole");
+
+    let mut excluded_names = ArgList::<String>::default();
+    flag_set.bind_mut_ref("exclude-names", false, &mut excluded_names, "select every column except those whose header is listed");
+
+    let mut field_names = ArgList::<String>::default();
+    flag_set.bind_mut_ref("fields-by-name", false, &mut field_names, "select the columns with these headers, in this order, e.g. name,email");
+
+    flag_set.add_exclusive_group(&["fields", "characters", "bytes", "graphemes"]);
+    flag_set.add_exclusive_group(&["fields", "exclude-names"]);
+    for other in ["fields", "characters", "bytes", "graphemes", "exclude-names"] {
+        flag_set.add_exclusive_group(&["fields-by-name", other]);
+    }
+
+    let mut retain_order = false;
+    flag_set.bind_aliases(&["retain-order", "repeat", "allow-duplicates"], &mut retain_order, "emit the selection in the given order, including duplicates");
+
+    let mut alpha_fields = false;
+    flag_set.bind_mut_ref("alpha-fields", false, &mut alpha_fields, "select fields by spreadsheet column letters, A=1");
+
+
+    let mut delemiter = Delimiter(None);
+    flag_set.bind_mut_ref("delimiter", true, &mut delemiter, "split fields on this instead of a tab");
+
+    flag_set.bind_mut_ref("delimiter-auto-per-file", false, &mut options.delimiter_auto_per_file, "guess the delimiter of each file from its first line");
+
+    let mut output_delimiter = String::new();
+    flag_set.bind_mut_ref("output-delimiter", false, &mut output_delimiter, "join the selected items with this string instead of the input delimiter");
+
+    let mut repeat_limit = 0usize;
+    flag_set.bind_mut_ref("delimiter-repeat-limit", false, &mut repeat_limit, "maximum number of fields split from a line, 0 means unlimited");
+
+    let mut lengths = false;
+    flag_set.bind_mut_ref("lengths", false, &mut lengths, "print the length of each selected item instead of its content");
+
+    let mut length_unit = LengthUnit::default();
+    flag_set.bind_mut_ref("length-unit", false, &mut length_unit, "unit used by --lengths, --min-length and --max-length: chars or bytes");
+
+    let mut min_length = 0usize;
+    flag_set.bind_mut_ref("min-length", false, &mut min_length, "skip lines shorter than this");
+
+    let mut max_length = 0usize;
+    flag_set.bind_mut_ref("max-length", false, &mut max_length, "skip lines longer than this, 0 means unlimited");
+
+    let mut count_only = false;
+    flag_set.bind_mut_ref("count-only", false, &mut count_only, "print how many items each line yields instead of the items");
+
+    let mut conditional_specs = ConditionalSpecs::default();
+    flag_set.bind_mut_ref("spec-if", false, &mut conditional_specs, "COUNT:LIST selects LIST on lines with COUNT fields, may be repeated");
+
+    let mut drop_unmatched = false;
+    flag_set.bind_mut_ref("drop-unmatched", false, &mut drop_unmatched, "drop lines matching no --spec-if instead of passing them through");
+
+    let mut formats = ArgList::<String>::default();
+    flag_set.bind_mut_ref("format", false, &mut formats, "pad field N to a width, e.g. 2:05 zero-fills field 2 to 5 characters, 3:<8 left-aligns");
+
+    let mut numeric_only = 0usize;
+    flag_set.bind_mut_ref("numeric-only", false, &mut numeric_only, "emit only lines whose field N is a number");
+
+    let mut non_numeric_only = 0usize;
+    flag_set.bind_mut_ref("non-numeric-only", false, &mut non_numeric_only, "emit only lines whose field N is missing or not a number");
+
+    flag_set.add_exclusive_group(&["numeric-only", "non-numeric-only"]);
+
+    let mut csv = false;
+    flag_set.bind_mut_ref("csv", false, &mut csv, "split fields as CSV, honoring delimiters and \"\" escapes inside double quotes");
+
+    let mut strip_quotes = false;
+    flag_set.bind_mut_ref("strip-quotes", false, &mut strip_quotes, "remove one pair of surrounding double quotes from each selected field");
+
+    let mut emit_empty_selection = Switch(true);
+    flag_set.bind_mut_ref("emit-empty-selection", false, &mut emit_empty_selection, "true prints a blank line for lines selecting nothing, false skips them");
+
+    let mut short_line_policy = ShortLinePolicy::default();
+    flag_set.bind_mut_ref("short-line-policy", false, &mut short_line_policy, "character and byte mode lines shorter than the selection: truncate, skip, pad with the --fill value or error");
+
+    let mut no_split_chars = false;
+    flag_set.bind_mut_ref_with_short("no-split-chars", "n", &mut no_split_chars, "with -b, do not emit parts of multi-byte characters");
+
+    let mut lines = ArgList::<String>::default();
+    flag_set.bind_mut_ref("lines", false, &mut lines, "only cut these lines, e.g. 2-4,7");
+
+    let mut expand_tabs = false;
+    flag_set.bind_mut_ref("expand-tabs", false, &mut expand_tabs, "with -c, expand tabs to spaces before cutting");
+    let mut tab_width = 8;
+    flag_set.bind_mut_ref("tab-width", false, &mut tab_width, "columns between the tab stops of --expand-tabs");
+
+    let mut json = false;
+    flag_set.bind_mut_ref("json", false, &mut json, "print the selection of each line as a JSON array of strings");
+
+    let mut append_delimiter = false;
+    flag_set.bind_mut_ref("append-delimiter", false, &mut append_delimiter, "end every non-empty output line with the output delimiter");
+
+    let mut keep_cr = false;
+    flag_set.bind_mut_ref("keep-cr", false, &mut keep_cr, "keep the carriage return of CRLF line endings as part of the line");
+
+    let mut whitespace = false;
+    flag_set.bind_mut_ref("whitespace", true, &mut whitespace, "split fields on runs of whitespace like awk and join them with a space");
+
+    let mut only_delimited = false;
+    flag_set.bind_mut_ref_with_short("only-delimited", "s", &mut only_delimited, "do not print lines without a delimiter");
+
+    let mut complement = false;
+    flag_set.bind_mut_ref("complement", false, &mut complement, "select every field, character or byte that is not listed");
+
+    let mut annotate = false;
+    flag_set.bind_mut_ref("annotate", false, &mut annotate, "print whole lines with the selected items wrapped in [ and ]");
+
+    let mut hash = false;
+    flag_set.bind_mut_ref("hash", false, &mut hash, "prefix each output line with a short hash of it");
+
+    let mut hash_algorithm = HashAlgorithm::default();
+    flag_set.bind_mut_ref("hash-algorithm", false, &mut hash_algorithm, "algorithm used by --hash: fnv or sip");
+
+    let mut hash_length = 8usize;
+    flag_set.bind_mut_ref("hash-length", false, &mut hash_length, "number of hex digits printed by --hash, at most 16");
+
+    let mut subfield = String::new();
+    flag_set.bind_mut_ref("subfield", false, &mut subfield, "split field N on SEP and keep sub-field M, written N:SEP:M");
 
-    let mut fields = ArgList::default();
-    flag_set.bind_mut_ref("fields", true, &mut fields, "");
+    flag_set.bind_aliases(&["file-headers", "with-filename", "H"], &mut options.file_headers, "print a ==> file <== header before the output of each of multiple files");
+    flag_set.bind_mut_ref("force-file-headers", false, &mut options.force_file_headers, "print file headers even for a single input");
 
+    flag_set.bind_mut_ref("transpose", false, &mut options.transpose, "turn the selected columns into rows");
+    flag_set.bind_mut_ref("table", false, &mut options.table, "print the selection as an aligned table with a rule below the header line");
+    flag_set.bind_mut_ref("fill", false, &mut options.fill, "value used to pad short rows");
+    flag_set.bind_mut_ref("group-by", false, &mut options.group_by, "join the output of consecutive lines sharing this field into one line");
 
-    let mut delemiter = '\t';
-    flag_set.bind_mut_ref("delimiter", true, &mut delemiter, "");
+    flag_set.bind_mut_ref("parallel-files", false, &mut options.parallel_files, "cut multiple files concurrently, the output keeps the file order");
+
+    flag_set.bind_mut_ref("interactive", false, &mut options.interactive, "list the header columns and prompt for the fields to select");
+
+    flag_set.bind_mut_ref("decompress", false, &mut options.decompress, "decompress inputs: auto picks by .gz, .zst or .bz2 extension, none, gzip, zstd or bz2");
+
+    flag_set.bind_mut_ref("zero-terminated", true, &mut options.zero_terminated, "lines are terminated by NUL instead of newline, in the input and the output");
+
+    flag_set.bind_mut_ref("force-stdin", false, &mut options.force_stdin, "read standard input even if it is a terminal");
+
+    flag_set.bind_mut_ref("output-encoding", false, &mut options.output_encoding, "encoding of the output: utf8, latin1, utf16le or utf16be");
+    flag_set.bind_mut_ref("unmappable", false, &mut options.unmappable, "handling of characters the output encoding lacks: replace, skip or error");
+
+    flag_set.bind_mut_ref("warn-missing-names", false, &mut options.warn_missing_names, "warn about excluded names missing from a header");
+
+    flag_set.bind_mut_ref("histogram", false, &mut options.histogram, "print how many lines have each field count instead of cutting");
+
+    // Each line is printed in one format, and the layouts of the whole input only print the items.
+    flag_set.add_exclusive_group(&["annotate", "count-only", "json"]);
+    flag_set.add_exclusive_group(&["transpose", "table", "group-by", "histogram"]);
+    for layout in ["transpose", "table", "group-by", "histogram"] {
+        for format in ["annotate", "count-only", "json", "hash"] {
+            flag_set.add_exclusive_group(&[layout, format]);
+        }
+    }
+    flag_set.bind_mut_ref("echo-spec", false, &mut options.echo_spec, "print the normalized selection to stderr before processing");
+
+    flag_set.bind_mut_ref("fail-on-empty", false, &mut options.fail_on_empty, "exit with status 1 if no line produced any output");
+
+    let mut version = false;
+    flag_set.bind_mut_ref_with_short("version", "V", &mut version, "print the version and exit");
+    flag_set.add_help();
 
     let remaining = match flag_set.parse(args) {
         Ok(files) => files,
+        Err(FlagError::HelpRequested) => {
+            return Ok(Parsed::Help(flag_set.usage()));
+        }
         Err(err) => {
             return Err(format!("Invalid arguments error: {err}"));
         }
     };
+    if version {
+        return Ok(Parsed::Version);
+    }
+
+    let fields = fields.inner.iter()
+        .map(|field| match alpha_fields {
+            true => FieldRange::parse_with(field, parse_column_letters),
+            false => field.parse(),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Invalid arguments error: {err}"))?;
+
+    let mut builder = CutterBuilder::new()
+        .complement(complement)
+        .only_delimited(only_delimited);
+    if !characters.inner.is_empty() {
+        builder = builder.characters(parse_ranges(&characters.inner)?);
+    }
+    if !graphemes.inner.is_empty() {
+        builder = builder.graphemes(parse_ranges(&graphemes.inner)?);
+    }
+    if !bytes.inner.is_empty() {
+        builder = builder.bytes(parse_ranges(&bytes.inner)?);
+    }
+    if !fields.is_empty() || characters.inner.is_empty() && graphemes.inner.is_empty() && bytes.inner.is_empty() {
+        builder = builder.fields(fields);
+    }
+    if let Some(delimiter) = delemiter.0 {
+        builder = builder.delimiter(delimiter);
+    }
+    if !output_delimiter.is_empty() {
+        builder = builder.output_delimiter(output_delimiter);
+    }
+    let mut cutter = builder.build().map_err(|err| format!("Invalid arguments error: {err}"))?;
+
+    if !conditional_specs.inner.is_empty() && !matches!(cutter.mode(), Mode::Fields(..)) {
+        return Err(String::from("Invalid arguments error: --spec-if only works in field mode"));
+    }
+    if !retain_order {
+        let mode = cutter.mode().clone().normalize();
+        cutter = cutter.with_mode(mode);
+    }
+
+    cutter = cutter
+        .with_count_only(count_only)
+        .with_csv(csv)
+        .with_strip_quotes(strip_quotes)
+        .with_whitespace(whitespace)
+        .with_strip_cr(!keep_cr)
+        .with_append_delimiter(append_delimiter)
+        .with_json(json)
+        .with_no_split_chars(no_split_chars)
+        .with_annotate(annotate)
+        .with_skip_empty(!emit_empty_selection.0)
+        .with_excluded_names(excluded_names.inner)
+        .with_field_names(field_names.inner)
+        .with_conditional_specs(conditional_specs.inner, drop_unmatched);
+    if repeat_limit > 0 {
+        cutter = cutter.with_max_fields(repeat_limit);
+    }
+    if !subfield.is_empty() {
+        let subfield = subfield.parse().map_err(|err| format!("Invalid arguments error: {err}"))?;
+        cutter = cutter.with_subfield(subfield);
+    }
+    for format in formats.inner {
+        let format = format.parse().map_err(|err| format!("Invalid arguments error: {err}"))?;
+        cutter = cutter.with_format(format);
+    }
+    let pad = options.fill.chars().next().unwrap_or(' ');
+    if short_line_policy == ShortLinePolicy::Pad && !pad.is_ascii() && cutter.is_byte_mode() {
+        return Err(String::from("Invalid arguments error: padding bytes needs an ASCII --fill value"));
+    }
+    cutter = cutter.with_short_line_policy(short_line_policy, pad);
+    if lengths {
+        cutter = cutter.with_lengths(length_unit);
+    }
+    if min_length > 0 || max_length > 0 {
+        let max_length = if max_length == 0 { usize::MAX } else { max_length };
+        cutter = cutter.with_line_length(min_length..=max_length, length_unit);
+    }
+    if numeric_only > 0 {
+        cutter = cutter.with_numeric_filter(numeric_only, true);
+    }
+    if non_numeric_only > 0 {
+        cutter = cutter.with_numeric_filter(non_numeric_only, false);
+    }
+    if hash {
+        cutter = cutter.with_hash(hash_algorithm, hash_length);
+    }
+    if !lines.inner.is_empty() {
+        cutter = cutter.with_lines(parse_line_ranges(&lines.inner)?);
+    }
+    if expand_tabs {
+        cutter = cutter.with_expand_tabs(tab_width);
+    }
+    cutter = cutter.with_terminator(options.terminator());
 
-    let cutter = Cutter::new(Mode::Fields(fields.inner, delemiter));
+    Ok(Parsed::Run(Box::new((cutter, options, remaining))))
+}
 
-    Ok((cutter, remaining))
+/// Runs with `out` behind a `BufWriter`, flushing once at the end instead of once per line.
+fn run_buffered(cutter: Cutter, options: Options, remaining: Vec<String>, out: impl Write) -> Result<bool, String> {
+    let mut out = io::BufWriter::new(out);
+    let matched = run(cutter, options, remaining, &mut out)?;
+    out.flush().map_err(|err| err.to_string())?;
+    Ok(matched)
 }
 
-fn run(cutter: Cutter, remaining: Vec<String>) -> Result<(), String> {
-    let mut readers: Vec<Box<dyn BufRead>> = Vec::new();
+/// Cuts every input into `out` and reports whether any non-empty line was written. Files that
+/// can not be opened are reported on stderr and skipped, `run` fails after cutting the others.
+fn run(mut cutter: Cutter, mut options: Options, mut remaining: Vec<String>, out: &mut impl Write) -> Result<bool, String> {
+    let mut readers: Vec<(String, Box<dyn BufRead + Send>)> = Vec::new();
 
-    if remaining.is_empty() || (remaining.len() == 1 && remaining[0] == "-") {
-        readers = vec![Box::new(io::BufReader::new(stdin()))]
+    if remaining.is_empty() {
+        if options.stdin_is_terminal && !options.force_stdin {
+            return Err(String::from("no input files given and standard input is a terminal, pass a file or use --force-stdin"));
+        }
+        remaining.push(String::from("-"));
     }
 
+    let files = remaining.len();
+    let mut failed = 0;
     for filepath in remaining {
-        match File::open(filepath.as_str()) {
-            Ok(file) => readers.push(Box::new(io::BufReader::new(file))),
+        match decompress::open(filepath.as_str(), options.decompress) {
+            Ok(reader) if filepath == "-" => readers.push((String::from("standard input"), reader)),
+            Ok(reader) => readers.push((filepath, reader)),
             Err(err) => {
-                return Err(format!("Can not open file {filepath}: {err}"));
+                eprintln!("Can not open file {filepath}: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    if let (true, Some(mut input)) = (options.interactive && !readers.is_empty(), options.prompt_input.take()) {
+        let (name, mut reader) = readers.remove(0);
+        let header = peek_header(&mut reader, &name, &options)?;
+        cutter = prompt_spec(cutter, &header, &mut input, &mut io::stderr())?;
+        readers.insert(0, (name, reader));
+    }
+
+    let file_headers = options.force_file_headers || (options.file_headers && readers.len() > 1);
+    let mut matched = false;
+    let mut histogram = HashMap::new();
+
+    let mut i = 0;
+    while !readers.is_empty() {
+        let batch = match options.parallel_files {
+            true => thread::available_parallelism().map_or(1, usize::from).min(readers.len()),
+            false => 1,
+        };
+        let mut outputs = Vec::new();
+
+        if batch == 1 {
+            let (name, reader) = readers.remove(0);
+            write_file_header(out, &name, i, file_headers, &options)?;
+            matched |= cut_file(&cutter, &options, &name, reader, out, &mut histogram)?;
+            i += 1;
+            continue;
+        }
+
+        thread::scope(|scope| {
+            let handles = readers.drain(..batch)
+                .map(|(name, reader)| {
+                    let (cutter, options) = (&cutter, &options);
+                    scope.spawn(move || {
+                        let mut buffer = Vec::new();
+                        let mut counts = HashMap::new();
+                        let result = cut_file(cutter, options, &name, reader, &mut buffer, &mut counts);
+                        (name, result, buffer, counts)
+                    })
+                })
+                .collect::<Vec<_>>();
+            outputs.extend(handles.into_iter().map(|handle| handle.join().expect("cutting a file panicked")));
+        });
+
+        for (name, result, buffer, counts) in outputs {
+            write_file_header(out, &name, i, file_headers, &options)?;
+            out.write_all(&buffer).map_err(|err| err.to_string())?;
+            matched |= result?;
+            for (fields, lines) in counts {
+                *histogram.entry(fields).or_insert(0) += lines;
             }
+            i += 1;
+        }
+    }
+
+    let mut histogram = histogram.into_iter().collect::<Vec<_>>();
+    histogram.sort_unstable();
+    for (fields, lines) in histogram {
+        matched = true;
+        write_line(out, &format!("{fields}\t{lines}"), &options)?;
+    }
+
+    if failed > 0 {
+        return Err(format!("Can not open {failed} of {files} files"));
+    }
+
+    Ok(matched)
+}
+
+/// Lists the columns of `header` on `prompt` and reads the fields to select from `input`.
+fn prompt_spec(cutter: Cutter, header: &str, input: &mut impl BufRead, prompt: &mut impl Write) -> Result<Cutter, String> {
+    let Mode::Fields(_, delimiter) = cutter.mode() else {
+        return Err(String::from("--interactive only works in field mode"));
+    };
+    let delimiter = delimiter.clone();
+
+    let mut listing = String::new();
+    for (i, column) in cutter.columns(header).iter().enumerate() {
+        listing.push_str(&format!("{}\t{column}\n", i + 1));
+    }
+    listing.push_str("fields: ");
+    prompt.write_all(listing.as_bytes())
+        .and_then(|_| prompt.flush())
+        .map_err(|err| err.to_string())?;
+
+    let mut spec = String::new();
+    input.read_line(&mut spec).map_err(|err| format!("Can not read the spec: {err}"))?;
+    let fields = split_list(spec.trim()).iter()
+        .map(|field| field.parse().map_err(|err| format!("Invalid spec {field}: {err}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(cutter.with_mode(Mode::Fields(fields, delimiter).normalize()))
+}
+
+fn write_file_header(out: &mut impl Write, name: &str, i: usize, file_headers: bool, options: &Options) -> Result<(), String> {
+    if !file_headers {
+        return Ok(());
+    }
+    if i > 0 {
+        write_line(out, "", options)?;
+    }
+    write_line(out, &format!("==> {name} <=="), options)
+}
+
+/// Reads the first line of `reader` without its terminator and puts it back in front of the rest.
+fn peek_header(reader: &mut Box<dyn BufRead + Send>, name: &str, options: &Options) -> Result<String, String> {
+    let mut header = Vec::new();
+    reader.read_until(options.terminator(), &mut header).map_err(|err| format!("Can not read file {name}: {err}"))?;
+    let rest = std::mem::replace(reader, Box::new(io::empty()));
+    let line = String::from_utf8_lossy(&header).trim_end_matches(options.terminator() as char).trim_end_matches('\r').to_string();
+    *reader = Box::new(io::Cursor::new(header).chain(rest));
+    Ok(line)
+}
+
+/// Cuts a single input into `out`, or adds its field counts to `histogram` with `--histogram`.
+fn cut_file(cutter: &Cutter, options: &Options, name: &str, mut reader: Box<dyn BufRead + Send>, out: &mut impl Write, histogram: &mut HashMap<usize, usize>) -> Result<bool, String> {
+    let mut matched = false;
+
+    let mut cutter = Cow::Borrowed(cutter);
+    if options.delimiter_auto_per_file {
+        if let Some(delimiter) = sniff_delimiter(&peek_header(&mut reader, name, options)?) {
+            cutter = Cow::Owned(cutter.into_owned().with_delimiter(delimiter.to_string()));
+        }
+    }
+
+    if options.warn_missing_names {
+        for missing in cutter.missing_names(&peek_header(&mut reader, name, options)?) {
+            eprintln!("warning: column {missing} not found in {name}");
         }
     }
 
-    for reader in readers {
-        let output = cutter.cut(reader);
-        for line in output {
-            println!("{line}");
+    if options.histogram {
+        let counts = cutter.field_count_histogram(reader).map_err(|err| format!("Can not read file {name}: {err}"))?;
+        for (fields, lines) in counts {
+            *histogram.entry(fields).or_insert(0) += lines;
+        }
+        return Ok(false);
+    }
+
+    if cutter.is_byte_mode() && options.output_encoding == Encoding::Utf8 && !options.transpose && !options.table && options.group_by == 0 {
+        let written = cutter.cut_bytes(reader, &mut *out, options.terminator()).map_err(|err| format!("Can not read file {name}: {err}"))?;
+        return Ok(written > 0);
+    }
+
+    let output = if options.transpose {
+        cutter.cut_transposed(reader, &options.fill)
+    } else if options.table {
+        cutter.cut_table(reader)
+    } else if options.group_by > 0 {
+        cutter.cut_grouped(reader, options.group_by)
+    } else if options.output_encoding == Encoding::Utf8 {
+        let mut out = MatchWriter { inner: out, terminator: options.terminator(), matched: false };
+        cutter.cut_to_writer(reader, &mut out).map_err(|err| format!("Can not cut file {name}: {err}"))?;
+        return Ok(out.matched);
+    } else {
+        cutter.cut(reader)
+    };
+    let output = output.map_err(|err| format!("Can not cut file {name}: {err}"))?;
+    for line in output {
+        matched |= !line.is_empty();
+        write_line(out, &line, options)?;
+    }
+
+    Ok(matched)
+}
+
+/// Passes writes through to `inner` and notes whether any of them contained more than line
+/// terminators.
+struct MatchWriter<W> {
+    inner: W,
+    terminator: u8,
+    matched: bool,
+}
+
+impl<W: Write> Write for MatchWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.matched |= buf[..written].iter().any(|byte| *byte != self.terminator);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn write_line(out: &mut impl Write, line: &str, options: &Options) -> Result<(), String> {
+    let bytes = options.output_encoding.encode(&format!("{line}{}", options.terminator() as char), options.unmappable)?;
+    out.write_all(&bytes).map_err(|err| err.to_string())
+}
+
+#[derive(Default)]
+struct ConditionalSpecs {
+    inner: Vec<ConditionalSpec>,
+}
+
+impl Value for ConditionalSpecs {
+    fn parse_from_string(&mut self, arg: &str) -> Result<(), String> {
+        self.inner.push(arg.parse()?);
+        Ok(())
+    }
+
+    fn try_activate(&mut self) -> Result<(), String> {
+        Err(String::from("bound value should be of type bool"))
+    }
+}
+
+/// A bool flag that takes its value as an argument, e.g. `--emit-empty-selection false`.
+struct Switch(bool);
+
+impl Value for Switch {
+    fn parse_from_string(&mut self, arg: &str) -> Result<(), String> {
+        self.0 = match arg {
+            "true" | "yes" => true,
+            "false" | "no" => false,
+            _ => return Err(format!("invalid value {arg}, expected true or false")),
+        };
+        Ok(())
+    }
+
+    fn try_activate(&mut self) -> Result<(), String> {
+        Err(String::from("bound value should be of type bool"))
+    }
+
+    fn default_value(&self) -> Option<String> {
+        Some(self.0.to_string())
+    }
+}
+
+/// The delimiter given with `-d`, `None` until it is set. An empty delimiter splits lines into
+/// single characters.
+struct Delimiter(Option<String>);
+
+impl FromStr for Delimiter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Delimiter(Some(unescape(s))))
+    }
+}
+
+impl Display for Delimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.as_deref().unwrap_or_default())
+    }
+}
+
+/// Replaces the escapes `\t`, `\n`, `\0` and `\\` with the characters they stand for, other
+/// backslashes are kept.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('0') => unescaped.push('\0'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => unescaped.extend(['\\', other]),
+            None => unescaped.push('\\'),
         }
     }
-    Ok(())
+    unescaped
+}
+
+/// Parses the ranges of -c, -g, -b and --lines, written like those of -f.
+fn parse_ranges(ranges: &[String]) -> Result<Vec<FieldRange>, String> {
+    ranges.iter()
+        .map(|range| range.parse().map_err(|err| format!("Invalid arguments error: {err}")))
+        .collect()
+}
+
+/// Parses the ranges of --lines, which can not count from the end of the input.
+fn parse_line_ranges(ranges: &[String]) -> Result<Vec<Range<usize>>, String> {
+    parse_ranges(ranges)?.iter()
+        .map(|range| match range.start > 0 && range.end > 0 {
+            true => Ok(range.start as usize - 1..range.end as usize),
+            false => Err(String::from("Invalid arguments error: --lines does not support ranges counted from the end")),
+        })
+        .collect()
+}
+
+fn parse_column_letters(letters: &str) -> Result<usize, String> {
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("invalid column letters: {letters}"));
+    }
+
+    letters.bytes().try_fold(0usize, |index, letter| {
+        let digit = (letter.to_ascii_uppercase() - b'A') as usize + 1;
+        index.checked_mul(26)
+            .and_then(|index| index.checked_add(digit))
+            .ok_or_else(|| format!("column letters out of range: {letters}"))
+    })
 }
 
 #[derive(Default)]
@@ -77,12 +728,12 @@ impl<T> ArgList<T> {
 impl<T> Value for ArgList<T>
     where T: FromStr, <T as FromStr>::Err: Display {
     fn parse_from_string(&mut self, arg: &str) -> Result<(), String> {
-        let arg = arg.strip_prefix('\"').unwrap_or(arg);
-        let arg = arg.strip_suffix('\"').unwrap_or(arg);
+        let arg = arg.strip_prefix('\"')
+            .and_then(|arg| arg.strip_suffix('\"'))
+            .filter(|arg| !arg.contains('\"'))
+            .unwrap_or(arg);
 
-        let separator = if arg.contains(',') { ',' } else { ' ' };
-
-        for i in arg.split(separator) {
+        for i in split_list(arg) {
             match i.parse() {
                 Ok(i) => self.inner.push(i),
                 Err(err) => return Err(err.to_string())
@@ -96,6 +747,36 @@ impl<T> Value for ArgList<T>
     }
 }
 
+fn split_list(arg: &str) -> Vec<String> {
+    let separator = if contains_unquoted(arg, ',') { ',' } else { ' ' };
+
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut quoted = false;
+    for c in arg.chars() {
+        match c {
+            '\"' => quoted = !quoted,
+            c if c == separator && !quoted => items.push(std::mem::take(&mut item)),
+            c => item.push(c),
+        }
+    }
+    items.push(item);
+
+    items
+}
+
+fn contains_unquoted(arg: &str, needle: char) -> bool {
+    let mut quoted = false;
+    for c in arg.chars() {
+        match c {
+            '\"' => quoted = !quoted,
+            c if c == needle && !quoted => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,4 +806,1013 @@ mod tests {
             assert_eq!(test.expected, actual.inner);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_column_letters() {
+        let tests = vec![
+            ("A", Ok(1)),
+            ("Z", Ok(26)),
+            ("AA", Ok(27)),
+            ("az", Ok(52)),
+            ("", Err(String::from("invalid column letters: "))),
+            ("A1", Err(String::from("invalid column letters: A1"))),
+        ];
+
+        for (letters, expected) in tests {
+            assert_eq!(expected, parse_column_letters(letters));
+        }
+    }
+
+    #[test]
+    fn test_alpha_fields() {
+        let args = ["--alpha-fields", "-f", "A,C"].map(String::from);
+        let (cutter, _, _) = create_run(args).unwrap();
+
+        assert_eq!(vec!["a\tc"], cutter.cut("a\tb\tc\n".as_bytes()).unwrap());
+    }
+
+    /// Parses `args` that are expected to cut files rather than print the usage or version.
+    fn create_run(args: impl IntoIterator<Item=String>) -> Result<(Cutter, Options, Vec<String>), String> {
+        create_cutter(args).map(|parsed| match parsed {
+            Parsed::Run(run) => *run,
+            Parsed::Help(_) | Parsed::Version => panic!("expected files to cut"),
+        })
+    }
+
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("cccut-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_file_headers() {
+        let first = temp_file("headers-first.tsv", "a\tb\n");
+        let second = temp_file("headers-second.tsv", "c\td\n");
+
+        struct TestCase {
+            args: Vec<String>,
+            expected: String,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec![String::from("--file-headers"), String::from("-f"), String::from("2"), first.clone(), second.clone()],
+                expected: format!("==> {first} <==\nb\n\n==> {second} <==\nd\n"),
+            },
+            TestCase {
+                args: vec![String::from("--file-headers"), String::from("-f"), String::from("2"), first.clone()],
+                expected: String::from("b\n"),
+            },
+            TestCase {
+                args: vec![String::from("--force-file-headers"), String::from("-f"), String::from("2"), first.clone()],
+                expected: format!("==> {first} <==\nb\n"),
+            },
+            TestCase {
+                args: vec![String::from("-Hf"), String::from("1"), first.clone(), second.clone()],
+                expected: format!("==> {first} <==\na\n\n==> {second} <==\nc\n"),
+            },
+            TestCase {
+                args: vec![String::from("--with-filename"), String::from("-f"), String::from("1"), second.clone()],
+                expected: String::from("c\n"),
+            },
+        ];
+
+        for test in tests {
+            let (cutter, options, remaining) = create_run(test.args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(test.expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_string_list() {
+        struct TestCase {
+            args: Vec<&'static str>,
+            expected: Vec<&'static str>,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec!["a,b,c"],
+                expected: vec!["a", "b", "c"],
+            },
+            TestCase {
+                args: vec!["a", "b"],
+                expected: vec!["a", "b"],
+            },
+            TestCase {
+                args: vec!["\"last, first\",email", "phone"],
+                expected: vec!["last, first", "email", "phone"],
+            },
+        ];
+        for test in tests {
+            let mut actual = ArgList::<String>::default();
+
+            for arg in test.args {
+                assert!(actual.parse_from_string(arg).is_ok());
+            }
+
+            assert_eq!(test.expected, actual.inner);
+        }
+    }
+
+    #[test]
+    fn test_repeated_string_list_flag() {
+        let mut names = ArgList::<String>::default();
+        let mut flag_set = FlagSet::default();
+        flag_set.bind_mut_ref("name", false, &mut names, "");
+
+        let args = ["--name", "a", "--name", "b,c"].map(String::from);
+        assert!(flag_set.parse(args).is_ok());
+
+        assert_eq!(vec!["a", "b", "c"], names.inner);
+    }
+
+    #[test]
+    fn test_terminal_stdin_hint() {
+        let args = ["-f", "1"].map(String::from);
+        let (cutter, mut options, remaining) = create_run(args).unwrap();
+        options.stdin_is_terminal = true;
+
+        let result = run(cutter, options, remaining, &mut Vec::new());
+
+        assert!(result.unwrap_err().contains("--force-stdin"));
+    }
+
+    #[test]
+    fn test_output_encoding() {
+        let input = temp_file("encoding.tsv", "café\t€\n");
+        let args = ["--output-encoding", "latin1", "-f", "1,2", input.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!(vec![b'c', b'a', b'f', 0xE9, b'\t', b'?', b'\n'], out);
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        let tests = vec![
+            ("3", Ok(vec![FieldRange::single(3)])),
+            ("2-4", Ok(vec![FieldRange::new(2, 4)])),
+            ("2-", Ok(vec![FieldRange::new(2, -1)])),
+            ("-3", Ok(vec![FieldRange::single(-3)])),
+            ("-3-", Ok(vec![FieldRange::new(-3, -1)])),
+            ("0-2", Err(String::from("Invalid arguments error: invalid field range 0-2, fields are numbered from 1"))),
+            ("-0", Err(String::from("Invalid arguments error: invalid field range -0, fields are numbered from 1"))),
+            ("a", Err(String::from("Invalid arguments error: invalid field a: invalid digit found in string"))),
+            ("5-3", Err(String::from("Invalid arguments error: invalid decreasing range 5-3"))),
+        ];
+
+        for (range, expected) in tests {
+            assert_eq!(expected, parse_ranges(&[String::from(range)]));
+        }
+    }
+
+    #[test]
+    fn test_selection_modes() {
+        struct TestCase {
+            args: Vec<&'static str>,
+            expected: Result<&'static str, String>,
+        }
+        let tests = vec![
+            TestCase {
+                args: vec!["-c", "2-3,5"],
+                expected: Ok("bce"),
+            },
+            TestCase {
+                args: vec!["--bytes", "1"],
+                expected: Ok("a"),
+            },
+            TestCase {
+                args: vec!["-f", "1", "-c", "2"],
+                expected: Err(String::from("Invalid arguments error: flags can not be used together: fields, characters")),
+            },
+            TestCase {
+                args: vec!["-f", "0"],
+                expected: Err(String::from("Invalid arguments error: invalid field range 0, fields are numbered from 1")),
+            },
+            TestCase {
+                args: vec!["-f", "1,0-2"],
+                expected: Err(String::from("Invalid arguments error: invalid field range 0-2, fields are numbered from 1")),
+            },
+        ];
+
+        for test in tests {
+            let result = create_run(test.args.iter().map(|a| a.to_string()))
+                .map(|(cutter, _, _)| cutter.cut("abcde\n".as_bytes()).unwrap().join("\n"));
+
+            assert_eq!(test.expected.map(String::from), result);
+        }
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        let tests = vec![
+            ("§", Ok("§")),
+            (",", Ok(",")),
+            ("e\u{301}", Ok("e\u{301}")),
+            ("::", Ok("::")),
+            ("", Ok("")),
+            ("\\t", Ok("\t")),
+            ("\\n", Ok("\n")),
+            ("\\0", Ok("\0")),
+            ("\\\\", Ok("\\")),
+            ("\\", Ok("\\")),
+            ("\\x", Ok("\\x")),
+        ];
+
+        for (arg, expected) in tests {
+            assert_eq!(expected.map(String::from), arg.parse::<Delimiter>().map(|delimiter| delimiter.0.unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_multibyte_delimiter() {
+        let args = ["-d", "§", "-f", "2"].map(String::from);
+        let (cutter, _, _) = create_run(args).unwrap();
+
+        assert_eq!(vec!["b"], cutter.cut("a§b§c\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_retain_order() {
+        let tests = vec![
+            (vec!["-f", "3,1"], "a\tc"),
+            (vec!["-f", "3,1", "--retain-order"], "c\ta"),
+            (vec!["-f", "3,1,1"], "a\tc"),
+            (vec!["-f", "3,1,1", "--retain-order"], "c\ta\ta"),
+            (vec!["-f", "2,1,2"], "a\tb"),
+            (vec!["-f", "2,1,2", "--repeat"], "b\ta\tb"),
+            (vec!["-f", "2,1,2", "--allow-duplicates"], "b\ta\tb"),
+        ];
+
+        for (args, expected) in tests {
+            let (cutter, _, _) = create_run(args.iter().map(|a| a.to_string())).unwrap();
+
+            assert_eq!(vec![expected], cutter.cut("a\tb\tc\n".as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_no_match_exit_code() {
+        let input = temp_file("no-match.tsv", "a\tb\n\n");
+
+        struct TestCase {
+            fields: &'static str,
+            expected: ExitCode,
+        }
+        let tests = vec![
+            TestCase {
+                fields: "2",
+                expected: ExitCode::SUCCESS,
+            },
+            TestCase {
+                fields: "5",
+                expected: ExitCode::from(NO_MATCH_EXIT_CODE),
+            },
+        ];
+
+        for test in tests {
+            let args = ["--fail-on-empty", "-f", test.fields, input.as_str()].map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let fail_on_empty = options.fail_on_empty;
+
+            let matched = run(cutter, options, remaining, &mut Vec::new()).unwrap();
+
+            assert_eq!(test.expected, exit_code(matched, fail_on_empty));
+        }
+        assert_eq!(ExitCode::SUCCESS, exit_code(false, false));
+    }
+
+    #[test]
+    fn test_exclude_names() {
+        let input = temp_file("exclude-names.csv", "name,password,email,ssn\nalice,secret,a@example.com,123\n");
+        let args = ["-d", ",", "--exclude-names", "password,ssn", "--warn-missing-names", input.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!("name,email\nalice,a@example.com\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_spec_if() {
+        let args = ["-d", ",", "--spec-if", "3:1,2", "--spec-if", "5:1,3,5", "--drop-unmatched"].map(String::from);
+        let (cutter, _, _) = create_run(args).unwrap();
+
+        let actual = cutter.cut("a,b,c\na,b,c,d,e\na,b\n".as_bytes()).unwrap();
+
+        assert_eq!(vec!["a,b", "a,c,e"], actual);
+    }
+
+    #[test]
+    fn test_binary_byte_mode() {
+        let path = env::temp_dir().join(format!("cccut-{}-binary.bin", std::process::id()));
+        std::fs::write(&path, [0xFF, 0xFE, b'a', b'\n', 0x00, 0x80, b'b', b'\n']).unwrap();
+        let args = ["-b", "1-2", path.to_str().unwrap()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!(vec![0xFF, 0xFE, b'\n', 0x00, 0x80, b'\n'], out);
+    }
+
+    #[test]
+    fn test_echo_spec() {
+        let tests = vec![
+            (vec!["--echo-spec", "-c", "1-3,2-5"], "1-5\n"),
+            (vec!["--echo-spec", "-f", "4,1,2,2"], "1-2,4\n"),
+            (vec!["--echo-spec", "-c", "-3-,1,2-"], "1-,-3-\n"),
+            (vec!["--echo-spec", "-f", "-3,1"], "1,-3\n"),
+            (vec!["-f", "4,1,2,2"], ""),
+        ];
+
+        for (args, expected) in tests {
+            let (cutter, options, _) = create_run(args.iter().map(|a| a.to_string())).unwrap();
+            let mut err = Vec::new();
+
+            echo_spec(&cutter, &options, &mut err).unwrap();
+
+            assert_eq!(expected, String::from_utf8(err).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let first = temp_file("histogram-first.csv", "a,b\nc,d,e\n");
+        let second = temp_file("histogram-second.csv", "f,g\nh\n");
+        let args = ["--histogram", "-d", ",", first.as_str(), second.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!("1\t1\n2\t2\n3\t1\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_parallel_files() {
+        let files = (0..8)
+            .map(|i| temp_file(&format!("parallel-{i}.csv"), &format!("{i},a\n{i},b\n")))
+            .collect::<Vec<_>>();
+        let args = ["--parallel-files", "--file-headers", "-d", ",", "-f", "1"].into_iter()
+            .map(String::from)
+            .chain(files.iter().cloned());
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        let expected = files.iter().enumerate()
+            .map(|(i, file)| format!("==> {file} <==\n{i}\n{i}\n"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_interactive() {
+        let input = temp_file("interactive.csv", "id,name,age\n1,ann,30\n2,bob,40\n");
+        let args = ["--interactive", "-d", ",", input.as_str()].map(String::from);
+        let (cutter, mut options, remaining) = create_run(args).unwrap();
+        options.prompt_input = Some(Box::new(io::Cursor::new("3,1\n")));
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!("id,age\n1,30\n2,40\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_prompt_spec() {
+        let cutter = Cutter::new(Mode::Fields(Vec::new(), String::from(",")));
+        let mut prompt = Vec::new();
+
+        let cutter = prompt_spec(cutter, "id,name", &mut "2\n".as_bytes(), &mut prompt).unwrap();
+
+        assert_eq!(&Mode::Fields(vec![FieldRange::single(2)], String::from(",")), cutter.mode());
+        assert_eq!("1\tid\n2\tname\nfields: ", String::from_utf8(prompt).unwrap());
+    }
+
+    #[test]
+    fn test_delimiter_auto_per_file() {
+        let first = temp_file("sniff-first.csv", "a,b;c\nd,e;f\n");
+        let second = temp_file("sniff-second.txt", "a|b,c|d\ne|f,g|h\n");
+        let args = ["--delimiter-auto-per-file", "-f", "2", first.as_str(), second.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!("b;c\ne;f\nb,c\nf,g\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_emit_empty_selection() {
+        let input = temp_file("empty-selection.csv", "a,b,c\nd\ne,f\n");
+        let tests = vec![
+            (vec![], "c\nd\n\n"),
+            (vec!["--emit-empty-selection", "true"], "c\nd\n\n"),
+            (vec!["--emit-empty-selection", "false"], "c\nd\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter()
+                .chain(["-d", ",", "-f", "3", input.as_str()])
+                .map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_emit_empty_selection_delimited() {
+        let input = temp_file("empty-selection-delimited.csv", "a,b,c\nd,e\ne,f\n");
+        let tests = vec![
+            (vec![], "c\n\n\n"),
+            (vec!["--emit-empty-selection", "true"], "c\n\n\n"),
+            (vec!["--emit-empty-selection", "false"], "c\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter()
+                .chain(["-d", ",", "-f", "3", input.as_str()])
+                .map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_byte_pad_not_ascii() {
+        let args = ["--short-line-policy", "pad", "--fill", "é", "-b", "2-4"].map(String::from);
+
+        let result = create_run(args);
+
+        assert_eq!("Invalid arguments error: padding bytes needs an ASCII --fill value", result.err().unwrap());
+    }
+
+    #[test]
+    fn test_short_line_policy() {
+        let input = temp_file("short-lines.txt", "abcdef\nab\n");
+        let tests = vec![
+            (vec![], Ok("bcd\nb\n")),
+            (vec!["--short-line-policy", "skip"], Ok("bcd\n")),
+            (vec!["--short-line-policy", "pad", "--fill", "."], Ok("bcd\nb..\n")),
+            (vec!["--short-line-policy", "error"], Err("line 2 is shorter than the selection")),
+            (vec!["--short-line-policy", "pad", "-b", "2-4"], Ok("bcd\nb  \n")),
+            (vec!["--short-line-policy", "error", "-b", "2-4"], Err("line 2 is shorter than the selection")),
+        ];
+
+        for (flags, expected) in tests {
+            let selection = match flags.contains(&"-b") {
+                true => vec![],
+                false => vec!["-c", "2-4"],
+            };
+            let args = flags.into_iter()
+                .chain(selection)
+                .chain([input.as_str()])
+                .map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            let result = run(cutter, options, remaining, &mut out);
+
+            match expected {
+                Ok(expected) => assert_eq!(expected, String::from_utf8(out).unwrap()),
+                Err(expected) => assert!(result.unwrap_err().contains(expected)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_field_range_list() {
+        let input = temp_file("field-ranges.tsv", "1\t2\t3\t4\t5\t6\t7\t8\n1\t2\t3\t4\n1\t2\n");
+        let args = ["-f", "1,3-5,7", input.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!("1\t3\t4\t5\t7\n1\t3\t4\n1\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_output_delimiter() {
+        let input = temp_file("output-delimiter.csv", "a,b,c\nabcdef\n");
+        let tests = vec![
+            (vec!["-d", ",", "-f", "1,3"], "a,c\nabcdef\n"),
+            (vec!["-d", ",", "-f", "1,3", "--output-delimiter", "|"], "a|c\nabcdef\n"),
+            (vec!["-c", "1-2,4", "--output-delimiter", ":"], "a,:,\nab:d\n"),
+            (vec!["-b", "1,3-4", "--output-delimiter", "::"], "a::b,\na::cd\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_complement() {
+        let input = temp_file("complement.tsv", "a\tb\tc\n");
+        let tests = vec![
+            (vec!["-f", "2", "--complement"], "a\tc\n"),
+            (vec!["-f", "1-2", "--complement"], "c\n"),
+            (vec!["-c", "2", "--complement"], "ab\tc\n"),
+            (vec!["-c", "2-3", "--complement", "--output-delimiter", "|"], "a|\tc\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_only_delimited() {
+        let input = temp_file("only-delimited.csv", "a,b,c\nno delimiter\n1,2\n");
+        let tests = vec![
+            (vec!["-d", ",", "-f", "2"], "b\nno delimiter\n2\n"),
+            (vec!["-d", ",", "-f", "2", "-s"], "b\n2\n"),
+            (vec!["-d", ",", "-f", "2", "--only-delimited"], "b\n2\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8_input() {
+        let input = env::temp_dir().join(format!("cccut-{}-invalid-utf8.csv", std::process::id()));
+        std::fs::write(&input, b"a,b\n\xff,c\n").unwrap();
+        let name = input.to_str().unwrap();
+        let args = ["-d", ",", "-f", "2", name].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        let result = run(cutter, options, remaining, &mut out);
+
+        assert!(result.unwrap_err().starts_with(&format!("Can not cut file {name}: ")));
+    }
+
+    #[test]
+    fn test_zero_terminated() {
+        let input = temp_file("zero-terminated.txt", "./a b/c.txt\0./d\ne/f.txt\0./g\0");
+        let tests = vec![
+            (vec!["-z", "-d", "/", "-f", "3"], "c.txt\0f.txt\0\0"),
+            (vec!["--zero-terminated", "-d", "/", "-f", "2-"], "a b/c.txt\0d\ne/f.txt\0g\0"),
+            (vec!["-z", "-b", "1-3"], "./a\0./d\0./g\0"),
+            (vec!["-z", "-d", "/", "-f", "3", "--output-encoding", "latin1"], "c.txt\0f.txt\0\0"),
+            (vec!["-z", "-d", "/", "-f", "2", "--group-by", "1"], ". a b,d\ne,g\0"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_no_split_chars() {
+        let input = temp_file("no-split-chars.txt", "héllo\n");
+        let tests = vec![
+            (vec!["-b", "3-4"], "\u{FFFD}l\n"),
+            (vec!["-b", "3-4", "-n"], "l\n"),
+            (vec!["-b", "1-2"], "h\u{FFFD}\n"),
+            (vec!["-b", "1-2", "-n"], "hé\n"),
+            (vec!["-b", "1-2", "--no-split-chars"], "hé\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8_lossy(&out));
+        }
+    }
+
+    #[test]
+    fn test_overlapping_ranges() {
+        let tests = vec![
+            (vec!["-c", "1-4"], "abcd"),
+            (vec!["-c", "1-3,2-4"], "abcd"),
+            (vec!["-c", "5-6,1-2"], "abef"),
+            (vec!["-b", "5-6,1-2,2"], "abef"),
+            (vec!["-c", "1-3,2-4", "--output-delimiter", ":"], "abcd"),
+            (vec!["-c", "5-6,1-2", "--output-delimiter", ":"], "ab:ef"),
+        ];
+
+        for (args, expected) in tests {
+            let (cutter, _, _) = create_run(args.iter().map(|a| a.to_string())).unwrap();
+
+            assert_eq!(vec![expected], cutter.cut("abcdefgh\n".as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_string_delimiter() {
+        let tests = vec![
+            (vec!["-d", "::", "-f", "2"], "a::b::c\n", "b"),
+            (vec!["-d", ", ", "-f", "2-"], "a, b, c\n", "b, c"),
+            (vec!["-d", ", ", "-f", "1,3", "--output-delimiter", "|"], "a, b, c\n", "a|c"),
+        ];
+
+        for (args, input, expected) in tests {
+            let (cutter, _, _) = create_run(args.iter().map(|a| a.to_string())).unwrap();
+
+            assert_eq!(vec![expected], cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let tests = vec![
+            (vec!["-w", "-f", "1,3"], "a c"),
+            (vec!["--whitespace", "-f", "2-"], "b c"),
+            (vec!["-w", "-f", "1,3", "--output-delimiter", ","], "a,c"),
+        ];
+
+        for (args, expected) in tests {
+            let (cutter, _, _) = create_run(args.iter().map(|a| a.to_string())).unwrap();
+
+            assert_eq!(vec![expected], cutter.cut("  a   b  c \n".as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fields_from_end() {
+        let input = temp_file("fields-from-end.csv", "a,b,c,d\na,b\na\n");
+        let tests = vec![
+            (vec!["-f", "-1"], "d\nb\na\n"),
+            (vec!["-f", "-2"], "c\na\na\n"),
+            (vec!["-f", "-2", "-s"], "c\na\n"),
+            (vec!["-f", "1,-1"], "a,d\na,b\na\n"),
+            (vec!["-f", "-3"], "b\n\na\n"),
+            (vec!["-f", "-2-"], "c,d\na,b\na\n"),
+            (vec!["-f", "1-2"], "a,b\na,b\na\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = ["-d", ","].into_iter()
+                .chain(flags)
+                .chain([input.as_str()])
+                .map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_characters_from_end() {
+        let input = temp_file("characters-from-end.txt", "abcdefgh\nxy\n");
+        let tests = vec![
+            (vec!["-c", "-3-"], "fgh\nxy\n"),
+            (vec!["-c", "-3"], "f\n\n"),
+            (vec!["-c", "1,-1"], "ah\nxy\n"),
+            (vec!["-c", "-3-", "--complement"], "abcde\n\n"),
+            (vec!["-c", "7-"], "gh\n\n"),
+            (vec!["-b", "-2-"], "gh\nxy\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_crlf() {
+        let input = temp_file("crlf.tsv", "a\tb\r\nc\td\r\n");
+        let tests = vec![
+            (vec!["-f", "2"], "b\nd\n"),
+            (vec!["-b", "3"], "b\nd\n"),
+            (vec!["-f", "2", "--keep-cr"], "b\r\nd\r\n"),
+            (vec!["-b", "3-4"], "b\r\nd\r\n"),
+            (vec!["-f", "2", "--keep-cr", "--transpose"], "b\r\td\r\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_append_delimiter() {
+        let input = temp_file("append-delimiter.csv", "a,b,c\nd,e\n");
+        let tests = vec![
+            (vec!["-f", "1,2"], "a,b\nd,e\n"),
+            (vec!["-f", "1,2", "--append-delimiter"], "a,b,\nd,e,\n"),
+            (vec!["-f", "1,2", "--append-delimiter", "--output-delimiter", ";"], "a;b;\nd;e;\n"),
+            (vec!["-f", "1,2", "--append-delimiter", "--complement"], "c,\n\n"),
+            (vec!["-f", "3", "--append-delimiter"], "c,\n\n"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = ["-d", ","].into_iter()
+                .chain(flags)
+                .chain([input.as_str()])
+                .map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_inline_flag_values() {
+        let args = ["--delimiter=,", "--fields=1,3", "--output-delimiter=a=b"].map(String::from);
+        let (cutter, _, _) = create_run(args).unwrap();
+
+        assert_eq!(vec!["xa=bz"], cutter.cut("x,y,z\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_attached_short_values() {
+        let args = ["-d,", "-f1,3"].map(String::from);
+        let (cutter, _, _) = create_run(args).unwrap();
+
+        assert_eq!(vec!["x,z"], cutter.cut("x,y,z\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_help() {
+        let parsed = create_cutter(["-d", ",", "--help"].map(String::from)).unwrap();
+
+        assert!(matches!(parsed, Parsed::Help(usage) if usage.contains("  -h, --help") && usage.contains("--version")));
+    }
+
+    #[test]
+    fn test_version() {
+        for args in [vec!["--version"], vec!["-V"], vec!["-d", ",", "-V", "file"]] {
+            let parsed = create_cutter(args.into_iter().map(String::from)).unwrap();
+
+            assert!(matches!(parsed, Parsed::Version));
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let present = temp_file("missing-present.csv", "a,b\nc,d\n");
+        let missing = env::temp_dir().join("cccut-missing-file.csv").to_string_lossy().into_owned();
+        let args = vec![String::from("-d"), String::from(","), String::from("-f"), String::from("2"), missing, present];
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        let result = run(cutter, options, remaining, &mut out);
+
+        assert_eq!(Err(String::from("Can not open 1 of 2 files")), result);
+        assert_eq!("b\nd\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_run_buffered() {
+        #[derive(Default)]
+        struct Counter {
+            writes: usize,
+            flushes: usize,
+        }
+
+        impl Write for Counter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.writes += 1;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.flushes += 1;
+                Ok(())
+            }
+        }
+
+        let contents = "a,b\n".repeat(1000);
+        let file = temp_file("buffered.csv", &contents);
+        let args = ["-d", ",", "-f", "2", file.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut counter = Counter::default();
+
+        run_buffered(cutter, options, remaining, &mut counter).unwrap();
+
+        assert_eq!(1, counter.flushes);
+        assert!(counter.writes < 10, "{} writes", counter.writes);
+    }
+
+    #[test]
+    fn test_delimiter_outside_field_mode() {
+        let tests = vec![
+            vec!["-c", "1-2", "-d", ","],
+            vec!["-d,", "-b", "1"],
+        ];
+
+        for args in tests {
+            let result = create_run(args.into_iter().map(String::from));
+
+            assert_eq!(
+                Some(String::from("Invalid arguments error: an input delimiter may be specified only when operating on fields")),
+                result.err(),
+            );
+        }
+
+        let (cutter, _, _) = create_run(["-c", "1-2"].map(String::from)).unwrap();
+        assert_eq!(vec!["ab"], cutter.cut("abc\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_output_mode_conflicts() {
+        let tests = vec![
+            (vec!["--json", "--count-only"], "count-only, json"),
+            (vec!["--annotate", "--json"], "annotate, json"),
+            (vec!["--json", "--table"], "table, json"),
+            (vec!["--transpose", "--json"], "transpose, json"),
+            (vec!["--group-by", "1", "--json"], "group-by, json"),
+            (vec!["--hash", "--table"], "table, hash"),
+            (vec!["--count-only", "--transpose"], "transpose, count-only"),
+            (vec!["--table", "--transpose"], "transpose, table"),
+            (vec!["--histogram", "--annotate"], "histogram, annotate"),
+        ];
+
+        for (flags, expected) in tests {
+            let args = ["-d", ",", "-f", "1"].into_iter().chain(flags).map(String::from);
+            let result = create_run(args);
+
+            assert_eq!(Some(format!("Invalid arguments error: flags can not be used together: {expected}")), result.err());
+        }
+    }
+
+    #[test]
+    fn test_only_delimited_outside_field_mode() {
+        let tests = vec![
+            (vec!["-c", "1", "-s"], "Invalid arguments error: suppressing non-delimited lines makes sense only when operating on fields"),
+            (vec!["-g", "1", "-s"], "Invalid arguments error: suppressing non-delimited lines makes sense only when operating on fields"),
+        ];
+
+        for (args, expected) in tests {
+            let result = create_run(args.into_iter().map(String::from));
+
+            assert_eq!(Some(String::from(expected)), result.err());
+        }
+    }
+
+    #[test]
+    fn test_csv_quoted_delimiter() {
+        let tests = vec![
+            ("\"a,b\",c\n", vec!["c"]),
+            ("\"a \"\"b\"\"\",c\n", vec!["c"]),
+            ("a,b,c\n", vec!["b"]),
+        ];
+
+        for (input, expected) in tests {
+            let (csv_cutter, _, _) = create_run(["--csv", "-d", ",", "-f", "2"].map(String::from)).unwrap();
+            let (plain_cutter, _, _) = create_run(["-d", ",", "-f", "2"].map(String::from)).unwrap();
+
+            assert_eq!(expected, csv_cutter.cut(input.as_bytes()).unwrap());
+            if !input.contains('"') {
+                assert_eq!(plain_cutter.cut(input.as_bytes()).unwrap(), csv_cutter.cut(input.as_bytes()).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_fields_by_name() {
+        let file = temp_file("by-name.csv", "id,name,email\n1,ann,ann@example.com\n");
+        let args = ["-d", ",", "--fields-by-name", "email,name", file.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        let mut out = Vec::new();
+
+        run(cutter, options, remaining, &mut out).unwrap();
+
+        assert_eq!("email,name\nann@example.com,ann\n", String::from_utf8(out).unwrap());
+
+        let args = ["--fields-by-name", "name", "-f", "1"].map(String::from);
+        assert!(create_run(args).is_err());
+
+        let args = ["--histogram", "-d", ",", "--fields-by-name", "zz", file.as_str()].map(String::from);
+        let (cutter, options, remaining) = create_run(args).unwrap();
+        assert!(run(cutter, options, remaining, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_empty_delimiter() {
+        let tests = vec![
+            (vec!["-d", "", "-f", "1,3"], "abc\n", vec!["ac"]),
+            (vec!["-d", "", "-f", "2-"], "añb\n", vec!["ñb"]),
+            (vec!["-d", "", "-f", "2", "-s"], "a\n\nxy\n", vec!["", "", "y"]),
+            (vec!["-d", "", "-f", "1,3", "--output-delimiter", ","], "abc\n", vec!["a,c"]),
+        ];
+
+        for (args, input, expected) in tests {
+            let (cutter, _, _) = create_run(args.into_iter().map(String::from)).unwrap();
+
+            assert_eq!(expected, cutter.cut(input.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_line_selection() {
+        let (cutter, _, _) = create_run(["-c", "1", "--lines", "1,3"].map(String::from)).unwrap();
+
+        assert_eq!(vec!["a", "e"], cutter.cut("ab\ncd\nef\ngh\n".as_bytes()).unwrap());
+
+        let result = create_run(["-c", "1", "--lines", "-3"].map(String::from));
+        assert_eq!("Invalid arguments error: --lines does not support ranges counted from the end", result.err().unwrap());
+    }
+
+    #[test]
+    fn test_decreasing_range() {
+        for args in [["-f5-3"], ["-c5-3"]] {
+            let err = create_run(args.map(String::from)).err().unwrap();
+
+            assert!(err.contains("decreasing range"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_byte_mode_options() {
+        let input = temp_file("byte-mode-options.txt", "é12\nabc\n");
+        let tests: Vec<(Vec<&str>, &[u8])> = vec![
+            (vec!["-b", "1-2", "--output-encoding", "latin1"], &[0xE9, b'\n', b'a', b'b', b'\n']),
+            (vec!["-b", "1-2", "--output-encoding", "latin1", "--unmappable", "error", "--lines", "2"], b"ab\n"),
+            (vec!["-b", "1,3", "--count-only"], b"2\n2\n"),
+            (vec!["-b", "1,3-4", "--lengths", "--output-delimiter", ","], b"1,2\n1,1\n"),
+            (vec!["-b", "3-4", "--min-length", "4", "--length-unit", "bytes"], b"12\n"),
+            (vec!["-b", "3", "--non-numeric-only", "1"], b"1\nc\n"),
+            (vec!["-b", "3-4", "--numeric-only", "1"], b""),
+        ];
+
+        for (flags, expected) in tests {
+            let args = flags.into_iter().chain([input.as_str()]).map(String::from);
+            let (cutter, options, remaining) = create_run(args).unwrap();
+            let mut out = Vec::new();
+
+            run(cutter, options, remaining, &mut out).unwrap();
+
+            assert_eq!(expected, out);
+        }
+    }
+
+    #[test]
+    fn test_spec_if_outside_field_mode() {
+        let args = ["-b", "1", "--spec-if", "3:1,2"].map(String::from);
+
+        let result = create_run(args);
+
+        assert_eq!("Invalid arguments error: --spec-if only works in field mode", result.err().unwrap());
+    }
+}
@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Split};
+
+use crate::{cut_next, Cutter};
+
+impl Cutter {
+    /// Like `cut_lines`, for async readers: cuts `reader` lazily into a stream of selections.
+    pub fn cut_async<R: AsyncBufRead + Unpin>(&self, reader: R) -> CutStream<'_, R> {
+        CutStream {
+            cutter: Cow::Borrowed(self),
+            records: reader.split(self.terminator),
+            line: 0,
+        }
+    }
+}
+
+/// Stream over the output of a reader returned by `Cutter::cut_async`. Read errors, invalid UTF-8
+/// and short lines under `ShortLinePolicy::Error` are yielded as errors.
+pub struct CutStream<'c, R> {
+    cutter: Cow<'c, Cutter>,
+    records: Split<R>,
+    line: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for CutStream<'_, R> {
+    type Item = io::Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let record = match ready!(Pin::new(&mut this.records).poll_next_segment(cx)) {
+                Ok(Some(record)) => record,
+                Ok(None) => return Poll::Ready(None),
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            };
+            this.line += 1;
+
+            let record = this.cutter.trim_record(&record, this.cutter.terminator);
+            let line = match std::str::from_utf8(record) {
+                Ok(line) => line,
+                Err(err) => return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)))),
+            };
+            if let Some(output) = cut_next(&mut this.cutter, this.line, line) {
+                return Poll::Ready(Some(output));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use crate::{FieldRange, Mode};
+
+    use super::*;
+
+    async fn collect<R: AsyncBufRead + Unpin>(mut stream: CutStream<'_, R>) -> Vec<io::Result<String>> {
+        let mut lines = Vec::new();
+        while let Some(line) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[test]
+    fn test_cut_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let tests: Vec<(Cutter, &[u8], Vec<&str>)> = vec![
+            (Cutter::new(Mode::Fields(vec![FieldRange::single(2)], String::from(","))), b"1,ann\n2,bob\n", vec!["ann", "bob"]),
+            (Cutter::new(Mode::Fields(vec![FieldRange::single(1)], String::from(","))).with_lines(vec![0..1, 2..3]), b"a\nb\nc", vec!["a", "c"]),
+            (Cutter::new(Mode::Fields(vec![FieldRange::single(2)], String::from("/"))).with_terminator(b'\0'), b"a/b\0c/d\0", vec!["b", "d"]),
+        ];
+
+        for (cutter, input, expected) in tests {
+            let lines = runtime.block_on(collect(cutter.cut_async(input)));
+
+            assert_eq!(expected, lines.into_iter().collect::<io::Result<Vec<_>>>().unwrap());
+        }
+
+        let cutter = Cutter::new(Mode::Characters(vec![FieldRange::single(1), FieldRange::single(3)]));
+        let lines = runtime.block_on(collect(cutter.cut_async(&b"abc\n\xff\n"[..])));
+        assert_eq!("ac", lines[0].as_ref().unwrap());
+        assert_eq!(io::ErrorKind::InvalidData, lines[1].as_ref().unwrap_err().kind());
+    }
+}